@@ -0,0 +1,423 @@
+use std::env;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::prelude::*;
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ControlSetup::from_args())
+            .add_startup_system(setup_control_label)
+            .add_system(update_control_label)
+            .add_system_set(
+                SystemSet::on_update(GameState::Serving)
+                    .with_system(choose_controls)
+            );
+    }
+}
+
+/// Who moves a paddle.
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+pub enum Control {
+    Human,
+    Ai(Difficulty),
+    Gamepad(Gamepad),
+}
+
+impl Control {
+    fn parse(value: &str) -> Option<Control> {
+        match value {
+            "human" => Some(Control::Human),
+            "easy" => Some(Control::Ai(Difficulty::Easy)),
+            "normal" | "ai" => Some(Control::Ai(Difficulty::Normal)),
+            "hard" => Some(Control::Ai(Difficulty::Hard)),
+            "gamepad" => Some(Control::Gamepad(Gamepad(0))),
+            _ => {
+                // `gamepad2` and so on, counting from 1 like the labels.
+                let number: usize = value.strip_prefix("gamepad")?.parse().ok()?;
+                Some(Control::Gamepad(Gamepad(number.checked_sub(1)?)))
+            }
+        }
+    }
+
+    /// After the AIs come each of `gamepads`, then back to human.
+    fn next(self, gamepads: &[Gamepad]) -> Control {
+        let gamepad = |index: usize| {
+            gamepads
+                .get(index)
+                .map_or(Control::Human, |gamepad| Control::Gamepad(*gamepad))
+        };
+
+        match self {
+            Control::Human => Control::Ai(Difficulty::Easy),
+            Control::Ai(Difficulty::Easy) => Control::Ai(Difficulty::Normal),
+            Control::Ai(Difficulty::Normal) => Control::Ai(Difficulty::Hard),
+            Control::Ai(Difficulty::Hard) => gamepad(0),
+            Control::Gamepad(current) => {
+                match gamepads.iter().position(|gamepad| *gamepad == current) {
+                    Some(index) => gamepad(index + 1),
+                    None => gamepad(0),
+                }
+            }
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Control::Human => "HUMAN".to_string(),
+            Control::Ai(difficulty) => format!("AI ({})", difficulty.label()),
+            Control::Gamepad(gamepad) => format!("GAMEPAD {}", gamepad.0 + 1),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "EASY",
+            Difficulty::Normal => "NORMAL",
+            Difficulty::Hard => "HARD",
+        }
+    }
+
+    pub fn easier(self) -> Difficulty {
+        match self {
+            Difficulty::Hard => Difficulty::Normal,
+            _ => Difficulty::Easy,
+        }
+    }
+
+    pub fn harder(self) -> Difficulty {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            _ => Difficulty::Hard,
+        }
+    }
+
+    /// Seconds between looks at the ball.
+    fn reaction_time(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.4,
+            Difficulty::Normal => 0.2,
+            Difficulty::Hard => 0.08,
+        }
+    }
+
+    /// Fastest the paddle will move, in the same units as `GameRules::paddle_speed`.
+    fn max_speed(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 180.0,
+            Difficulty::Normal => 250.0,
+            Difficulty::Hard => 300.0,
+        }
+    }
+
+    /// Largest aiming mistake, as a fraction of the paddle's height.
+    fn aim_error(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.9,
+            Difficulty::Normal => 0.5,
+            Difficulty::Hard => 0.2,
+        }
+    }
+}
+
+/// Controls each side starts with, from `--left <control>` and
+/// `--right <control>`: `human`, `easy`, `normal`, `hard`, or `gamepad` for
+/// the first gamepad and `gamepad2` and so on for the others.
+pub struct ControlSetup {
+    pub left: Control,
+    pub right: Control,
+}
+
+impl ControlSetup {
+    pub fn from_args() -> Self {
+        let mut setup = ControlSetup {
+            left: Control::Human,
+            right: Control::Human,
+        };
+        let mut args = env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            let side = match arg.as_str() {
+                "--left" => &mut setup.left,
+                "--right" => &mut setup.right,
+                _ => continue,
+            };
+            if let Some(control) = args.next().as_deref().and_then(Control::parse) {
+                *side = control;
+            }
+        }
+
+        setup
+    }
+}
+
+#[derive(Component)]
+struct ControlLabel;
+
+/// Where the ball will cross `target_x`, bouncing off the top and bottom
+/// walls on the way, or `None` if it's heading the other way.
+pub fn predict_ball_y(
+    position: Vec2,
+    velocity: Vec2,
+    target_x: f32,
+    bottom: f32,
+    top: f32,
+) -> Option<f32> {
+    let distance = target_x - position.x;
+    if velocity.x == 0.0 || distance.signum() != velocity.x.signum() {
+        return None;
+    }
+
+    let y = position.y + velocity.y * distance / velocity.x;
+    let span = top - bottom;
+    if span <= 0.0 {
+        return Some(bottom);
+    }
+
+    // Unfold the bounces: the path zig-zags through mirrored copies of the court.
+    let folded = (y - bottom).rem_euclid(2.0 * span);
+    Some(if folded > span {
+        top - (folded - span)
+    } else {
+        bottom + folded
+    })
+}
+
+/// Tracks where the ball is going, with a difficulty-dependent delay, top
+/// speed and aiming error. Its mistakes come from its own seeded RNG, so
+/// they don't depend on how often the other paddle's controller draws.
+pub struct AiController {
+    difficulty: Difficulty,
+    reaction_timer: Timer,
+    target_y: Option<f32>,
+    rng: ChaCha8Rng,
+}
+
+impl AiController {
+    pub fn new(difficulty: Difficulty, seed: u64) -> Self {
+        AiController {
+            difficulty,
+            reaction_timer: Timer::from_seconds(0.0, false),
+            target_y: None,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl PaddleController for AiController {
+    fn intent(&mut self, context: &ControlContext) -> PaddleIntent {
+        let half_ball = context.ball_size / 2.0;
+        let bottom = context.court.y - context.court_size.y / 2.0 + half_ball.y;
+        let top = context.court.y + context.court_size.y / 2.0 - half_ball.y;
+
+        // Only look at the ball every so often, then commit to that plan.
+        self.reaction_timer.tick(context.delta);
+        if self.reaction_timer.finished() || self.target_y.is_none() {
+            let face_x = context.paddle.x
+                - context.paddle.x.signum() * (context.paddle_size.x / 2.0 + half_ball.x);
+            let error = self.rng.gen_range(-1.0_f32..=1.0)
+                * self.difficulty.aim_error()
+                * context.paddle_size.y
+                / 2.0;
+            let target = predict_ball_y(context.ball, context.ball_velocity, face_x, bottom, top)
+                // Drift back to the middle while the ball is going away.
+                .unwrap_or(context.court.y);
+
+            self.target_y = Some(target + error);
+            self.reaction_timer = Timer::from_seconds(self.difficulty.reaction_time(), false);
+        }
+
+        let distance = self.target_y.unwrap_or(context.paddle.y) - context.paddle.y;
+        let full_step = context.paddle_speed * context.delta.as_secs_f32();
+        let max_intent = self.difficulty.max_speed() / context.paddle_speed;
+        let vertical = if full_step > 0.0 {
+            (distance / full_step).clamp(-max_intent, max_intent)
+        } else {
+            0.0
+        };
+
+        // Leaves serving to whoever's at the keyboard.
+        PaddleIntent {
+            vertical,
+            push: 0.0,
+            serve: context.keys.pressed(context.bindings.serve),
+        }
+    }
+}
+
+/// `1` and `2` cycle the left and right paddles through human, the AI
+/// difficulties and the gamepads plugged in while waiting to serve.
+fn choose_controls(
+    keys: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    mut paddle_q: Query<(&Player, &mut Control)>,
+) {
+    let controls: Vec<_> = paddle_q.iter().map(|(player, control)| (*player, *control)).collect();
+
+    for (player, mut control) in paddle_q.iter_mut() {
+        let key = match player {
+            Player::Left => KeyCode::Key1,
+            Player::Right => KeyCode::Key2,
+        };
+        if keys.just_pressed(key) {
+            *control = next_control(*player, &controls, gamepads.iter().copied());
+        }
+    }
+}
+
+/// The control `player` gets next out of `controls`, skipping `gamepads` that
+/// already drive the other paddle, as each drives one paddle at most.
+pub fn next_control(
+    player: Player,
+    controls: &[(Player, Control)],
+    gamepads: impl IntoIterator<Item = Gamepad>,
+) -> Control {
+    let free: Vec<_> = gamepads
+        .into_iter()
+        .filter(|gamepad| {
+            !controls.iter().any(|(other, control)| {
+                *other != player && *control == Control::Gamepad(*gamepad)
+            })
+        })
+        .collect();
+    let current = controls
+        .iter()
+        .find(|(other, _)| *other == player)
+        .map_or(Control::Human, |(_, control)| *control);
+    current.next(&free)
+}
+
+fn setup_control_label(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(5.0),
+                    ..default()
+                },
+                ..default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                    font_size: 20.0,
+                    color: Color::rgb(0.6, 0.6, 0.6),
+                },
+                TextAlignment::default(),
+            ),
+            ..default()
+        })
+        .insert(ControlLabel);
+}
+
+fn update_control_label(
+    paddle_q: Query<(&Player, &Control), Changed<Control>>,
+    all_paddles_q: Query<(&Player, &Control)>,
+    mut label_q: Query<&mut Text, With<ControlLabel>>,
+) {
+    if paddle_q.is_empty() {
+        return;
+    }
+
+    let mut labels = [String::new(), String::new()];
+    for (player, control) in all_paddles_q.iter() {
+        match player {
+            Player::Left => labels[0] = control.label(),
+            Player::Right => labels[1] = control.label(),
+        }
+    }
+    label_q.single_mut().sections[0].value =
+        format!("[1] LEFT: {}   [2] RIGHT: {}", labels[0], labels[1]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: Option<f32>, expected: f32) {
+        let actual = actual.expect("should reach the target");
+        assert!((actual - expected).abs() < 1e-3, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn predictions_fold_off_the_walls() {
+        let start = Vec2::ZERO;
+        let (bottom, top) = (-10.0, 10.0);
+
+        assert_near(predict_ball_y(start, Vec2::new(100.0, 0.0), 50.0, bottom, top), 0.0);
+        // Off the top once.
+        assert_near(predict_ball_y(start, Vec2::new(100.0, 100.0), 15.0, bottom, top), 5.0);
+        // Top, bottom, top, then the bottom again before coming back up.
+        assert_near(predict_ball_y(start, Vec2::new(100.0, 100.0), 75.0, bottom, top), -5.0);
+        // Going left and steeply down: four bounces before it gets there.
+        assert_near(predict_ball_y(start, Vec2::new(-100.0, -300.0), -25.0, bottom, top), 5.0);
+    }
+
+    #[test]
+    fn no_prediction_for_a_ball_going_away() {
+        let (bottom, top) = (-10.0, 10.0);
+
+        assert_eq!(predict_ball_y(Vec2::ZERO, Vec2::new(-100.0, 50.0), 50.0, bottom, top), None);
+        assert_eq!(predict_ball_y(Vec2::ZERO, Vec2::new(100.0, 50.0), -50.0, bottom, top), None);
+        assert_eq!(predict_ball_y(Vec2::ZERO, Vec2::new(0.0, 50.0), 50.0, bottom, top), None);
+    }
+
+    #[test]
+    fn a_court_no_taller_than_the_ball_pins_it_to_the_bottom() {
+        let velocity = Vec2::new(100.0, 70.0);
+
+        assert_eq!(predict_ball_y(Vec2::ZERO, velocity, 50.0, 5.0, 5.0), Some(5.0));
+        assert_eq!(predict_ball_y(Vec2::ZERO, velocity, 50.0, 5.0, 2.0), Some(5.0));
+    }
+
+    #[test]
+    fn gamepads_driving_the_other_paddle_are_skipped() {
+        let gamepads = [Gamepad(0), Gamepad(1), Gamepad(2)];
+        let controls = [
+            (Player::Left, Control::Gamepad(Gamepad(1))),
+            (Player::Right, Control::Ai(Difficulty::Hard)),
+        ];
+
+        assert_eq!(
+            next_control(Player::Right, &controls, gamepads),
+            Control::Gamepad(Gamepad(0))
+        );
+
+        let controls = [controls[0], (Player::Right, Control::Gamepad(Gamepad(0)))];
+        assert_eq!(
+            next_control(Player::Right, &controls, gamepads),
+            Control::Gamepad(Gamepad(2))
+        );
+    }
+
+    #[test]
+    fn with_every_free_gamepad_taken_its_back_to_human() {
+        let controls = [
+            (Player::Left, Control::Gamepad(Gamepad(0))),
+            (Player::Right, Control::Ai(Difficulty::Hard)),
+        ];
+
+        assert_eq!(next_control(Player::Right, &controls, [Gamepad(0)]), Control::Human);
+        // A paddle's own gamepad doesn't count as taken.
+        assert_eq!(
+            next_control(Player::Left, &controls, [Gamepad(0), Gamepad(1)]),
+            Control::Gamepad(Gamepad(1))
+        );
+    }
+}
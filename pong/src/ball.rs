@@ -9,23 +9,24 @@ pub struct BallPlugin;
 impl Plugin for BallPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system(setup_ball)
-            .add_system(bounce)
             .add_system_set(
                 SystemSet::on_enter(GameState::Serving)
                     .with_system(serving_ball_enter)
             )
             .add_system_set(
                 SystemSet::on_update(GameState::Serving)
-                    .with_system(serve_ball)
                     .with_system(blink_ball)
             )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(playing_ball_enter)
+            )
             .add_system_set(
                 SystemSet::on_enter(GameState::Scored)
                     .with_system(scored_ball_enter)
             )
             .add_system_set(
                 SystemSet::on_update(GameState::Scored)
-                    .with_system(scored_ball_update)
                     .with_system(blink_ball)
             )
             .add_system_set(
@@ -34,17 +35,37 @@ impl Plugin for BallPlugin {
             )
             .add_system_set(
                 SystemSet::on_update(GameState::Resetting)
-                    .with_system(reset_ball_update)
                     .with_system(blink_ball)
             )
-            .add_system_set(
-                SystemSet::on_update(GameState::Playing)
+            .add_system_set_to_stage(
+                PhysicsStage,
+                SystemSet::new()
+                    .with_run_criteria(while_serving)
+                    .with_system(serve_ball.after(paddle_control))
+            )
+            .add_system_set_to_stage(
+                PhysicsStage,
+                SystemSet::new()
+                    .with_run_criteria(while_playing)
                     .with_system(ball_movement.after(paddle_control))
-            );
+            )
+            .add_system_set_to_stage(
+                PhysicsStage,
+                SystemSet::new()
+                    .with_run_criteria(while_scored)
+                    .with_system(scored_ball_update)
+            )
+            .add_system_set_to_stage(
+                PhysicsStage,
+                SystemSet::new()
+                    .with_run_criteria(while_resetting)
+                    .with_system(reset_ball_update)
+            )
+            .add_system_to_stage(PhysicsStage, bounce.after(court_collisions));
     }
 }
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct Ball {
     pub hold_timer: Timer,
     pub blink_timer: Timer,
@@ -57,7 +78,12 @@ impl Ball {
     }
 }
 
-fn setup_ball(mut commands: Commands, windows: Res<Windows>) {
+fn setup_ball(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    rules: Res<GameRules>,
+    mut rng: ResMut<GameRng>,
+) {
     let window = windows.get_primary().unwrap();
     let ball_radius = Ball::calc_radius(window.width());
     let size = Vec2::splat(ball_radius * 2.0);
@@ -84,7 +110,8 @@ fn setup_ball(mut commands: Commands, windows: Res<Windows>) {
             blink_timer: Timer::from_seconds(0.25, false),
             reset_timer: Timer::from_seconds(2.0, false)
         })
-        .insert(Velocity::random())
+        .insert(Velocity::random(&rules, &mut rng))
+        .insert(Interpolated::default())
         .insert(Visibility { is_visible: true })
         .insert(name)
         .insert(player)
@@ -94,37 +121,136 @@ fn setup_ball(mut commands: Commands, windows: Res<Windows>) {
         });
 }
 
-pub fn ball_movement(mut ball_q: Query<(&Velocity, &mut Transform), With<Ball>>, time: Res<Time>) {
-    let (velocity, mut transform) = ball_q.single_mut();
+/// Most bounces the ball makes in one frame before it gives up on the rest
+/// of its move.
+const MAX_BOUNCES: usize = 4;
+
+/// Sweeps the ball along its velocity, stopping at the first thing it would
+/// touch and bouncing from there, so nothing gets skipped over at speed.
+pub fn ball_movement(
+    mut ball_q: Query<(Entity, &mut Velocity, &mut Transform, &BoundingBox), With<Ball>>,
+    paddle_q: Query<(&Transform, &BoundingBox, &Velocity), (With<Player>, Without<Ball>)>,
+    court_q: Query<(&Transform, &BoundingBox), (With<Court>, Without<Ball>, Without<Player>)>,
+    mut collision_event: EventWriter<CollisionEvent>,
+    rules: Res<GameRules>,
+) {
+    let (entity, mut velocity, mut transform, bbox) = ball_q.single_mut();
+    let (court_t, court_bbox) = court_q.single();
+    let half_size = bbox.as_vec2() / 2.0;
+    let bottom = court_t.translation.y - court_bbox.half_height();
+    let top = court_t.translation.y + court_bbox.half_height();
+
+    let mut position = transform.translation.xy();
+    let mut remaining = TICK_SECONDS;
+    for _ in 0..MAX_BOUNCES {
+        let displacement = Vec2::new(velocity.x, velocity.y) * remaining;
+
+        let mut nearest = sweep_walls(position, half_size, displacement, bottom, top)
+            .map(|hit| (hit, Velocity { x: 0.0, y: 0.0 }, None));
+        for (paddle_t, paddle_bbox, paddle_v) in paddle_q.iter() {
+            let paddle = paddle_t.translation.xy();
+            let paddle_half = paddle_bbox.as_vec2() / 2.0;
+            let hit = match sweep_aabb(position, half_size, displacement, paddle, paddle_half) {
+                Some(hit) => hit,
+                None => continue,
+            };
+            if nearest.map_or(false, |(nearest, ..)| nearest.time <= hit.time) {
+                continue;
+            }
+
+            let hit_offset = if hit.normal.x != 0.0 {
+                let reach = paddle_half.y + half_size.y;
+                Some(((hit.position.y - paddle.y) / reach).clamp(-1.0, 1.0))
+            } else {
+                None
+            };
+            nearest = Some((hit, *paddle_v, hit_offset));
+        }
+
+        let (hit, other_velocity, hit_offset) = match nearest {
+            Some(nearest) => nearest,
+            None => {
+                position += displacement;
+                break;
+            }
+        };
+
+        position = hit.position;
+        remaining *= 1.0 - hit.time;
+        let collision = CollisionEvent {
+            entity,
+            normal: hit.normal,
+            other_velocity,
+            hit_offset,
+        };
+        *velocity = rebound(&velocity, &collision, &rules);
+        collision_event.send(collision);
+    }
 
-    transform.translation.x += velocity.x * time.delta_seconds();
-    transform.translation.y += velocity.y * time.delta_seconds();
+    transform.translation.x = position.x;
+    transform.translation.y = position.y;
 }
 
-fn bounce(
-    mut bounceables: Query<&mut Velocity>,
+/// Bounces anything but the ball, which `ball_movement` bounces as it goes.
+pub fn bounce(
+    mut bounceables: Query<&mut Velocity, Without<Ball>>,
     mut collision_event: EventReader<CollisionEvent>,
+    rules: Res<GameRules>,
 ) {
     for collision in collision_event.iter() {
         if let Ok(mut velocity) = bounceables.get_mut(collision.entity) {
-            if velocity.x > 0.0 && collision.location.x > 0.0 {
-                velocity.x *= -1.0;
-            } else if velocity.x < 0.0 && collision.location.x < 0.0 {
-                velocity.x *= -1.0;
-            }
+            *velocity = rebound(&velocity, collision, &rules);
+        }
+    }
+}
 
-            if velocity.y > 0.0 && collision.location.y > 0.0 {
-                velocity.y *= -1.0;
-            } else if velocity.y < 0.0 && collision.location.y < 0.0 {
-                velocity.y *= -1.0;
-            }
+/// The velocity after a collision: reflected off the surface and nudged by
+/// whatever was hit, or angled by `paddle_rebound` off a paddle's face.
+pub fn rebound(velocity: &Velocity, collision: &CollisionEvent, rules: &GameRules) -> Velocity {
+    if let Some(offset) = collision.hit_offset {
+        return paddle_rebound(velocity, collision, offset, rules);
+    }
 
-            velocity.y += collision.other_velocity.y;
-            velocity.x += collision.other_velocity.x;
-        }
+    let mut rebound = Vec2::new(velocity.x, velocity.y);
+    let into = rebound.dot(collision.normal);
+    if into < 0.0 {
+        rebound -= 2.0 * into * collision.normal;
+    }
+    rebound += Vec2::new(collision.other_velocity.x, collision.other_velocity.y);
+
+    Velocity {
+        x: rebound.x,
+        y: rebound.y,
     }
 }
 
+/// Sends the ball back off a paddle's face at an angle set by how far from
+/// the middle it hit, a little faster than it came in.
+fn paddle_rebound(
+    velocity: &Velocity,
+    collision: &CollisionEvent,
+    offset: f32,
+    rules: &GameRules,
+) -> Velocity {
+    let speed = (Vec2::new(velocity.x, velocity.y).length() * rules.speed_up)
+        .min(rules.max_ball_speed);
+    let angle = offset * rules.max_bounce_angle;
+
+    let rebound = Vec2::new(collision.normal.x * angle.cos(), angle.sin()) * speed
+        + Vec2::new(
+            collision.other_velocity.x,
+            collision.other_velocity.y * rules.paddle_spin,
+        );
+    let rebound = rebound.clamp_length_max(rules.max_ball_speed);
+
+    Velocity {
+        x: rebound.x,
+        y: rebound.y,
+    }
+}
+
+/// Plays the ball back to the middle, with `reset_ball_update` setting where
+/// the animation is up to from the physics ticks.
 fn reset_ball_enter(
     mut ball_q: Query<(&mut Ball, &Transform, &Name, &mut AnimationPlayer)>,
     court_q: Query<&Transform, (With<Court>, Without<Ball>)>,
@@ -147,8 +273,8 @@ fn reset_ball_enter(
     );
     let animation = animations.add(clip);
     player.play(animation);
-    ball.reset_timer.reset();
-    ball.reset_timer.unpause();
+    player.pause();
+    player.set_elapsed(ball.reset_timer.elapsed_secs());
 
     ball.blink_timer.set_duration(Duration::from_secs_f32(0.33));
     ball.blink_timer.reset();
@@ -156,17 +282,16 @@ fn reset_ball_enter(
 }
 
 fn reset_ball_update(
-    mut ball_q: Query<&mut Ball>,
-    mut state: ResMut<State<GameState>>,
-    time: Res<Time>,
+    mut ball_q: Query<(&mut Ball, &mut AnimationPlayer)>,
+    mut sim: ResMut<SimState>,
 ) {
-    let mut ball = ball_q.single_mut();
-    ball.reset_timer.tick(time.delta());
+    let (mut ball, mut player) = ball_q.single_mut();
+    ball.reset_timer.tick(Duration::from_secs_f32(TICK_SECONDS));
+    player.set_elapsed(ball.reset_timer.elapsed_secs());
+
     if ball.reset_timer.finished() {
-        ball.reset_timer.pause();
         ball.reset_timer.reset();
-        state.set(GameState::Serving).unwrap();
-        return;
+        sim.0 = GameState::Serving;
     }
 }
 
@@ -194,31 +319,44 @@ fn serving_ball_enter(
     ball.blink_timer.unpause();
 }
 
-fn serve_ball(
-    mut ball_q: Query<(&mut Ball, &mut Velocity, &mut Visibility)>,
-    keys: Res<Input<KeyCode>>,
-    mut state: ResMut<State<GameState>>,
+/// Serves as soon as either side asks to, from the middle of the court,
+/// towards whoever the ball was heading for last.
+pub fn serve_ball(
+    mut ball_q: Query<(&mut Transform, &mut Velocity), With<Ball>>,
+    court_q: Query<&Transform, (With<Court>, Without<Ball>)>,
+    intent_q: Query<&PaddleIntent>,
+    rules: Res<GameRules>,
+    mut rng: ResMut<GameRng>,
+    mut sim: ResMut<SimState>,
 ) {
-    let (mut ball, mut velocity, mut visiblity) = ball_q.single_mut();
-    if keys.just_released(KeyCode::Space) {
-        let was_negative = velocity.x < 0.0;
-        *velocity = Velocity::random();
-        if (velocity.x < 0.0) != was_negative {
-            velocity.x *= -1.0;
-        }
-        ball.blink_timer.pause();
-        visiblity.is_visible = true;
-        state.set(GameState::Playing).unwrap();
+    if !intent_q.iter().any(|intent| intent.serve) {
+        return;
     }
+
+    let (mut transform, mut velocity) = ball_q.single_mut();
+    // The reset animation only gets it close, depending on the frame rate.
+    let court_center = court_q.single().translation;
+    transform.translation.x = court_center.x;
+    transform.translation.y = court_center.y;
+
+    let was_negative = velocity.x < 0.0;
+    *velocity = Velocity::random(&rules, &mut rng);
+    if (velocity.x < 0.0) != was_negative {
+        velocity.x *= -1.0;
+    }
+    sim.0 = GameState::Playing;
+}
+
+fn playing_ball_enter(mut ball_q: Query<(&mut Ball, &mut Visibility)>) {
+    let (mut ball, mut visibility) = ball_q.single_mut();
+    ball.blink_timer.pause();
+    visibility.is_visible = true;
 }
 
 fn scored_ball_enter(
     mut ball_q: Query<&mut Ball>,
 ) {
     let mut ball = ball_q.single_mut();
-    ball.hold_timer.reset();
-    ball.hold_timer.unpause();
-
     ball.blink_timer.reset();
     ball.blink_timer.set_duration(Duration::from_secs_f32(0.08));
     ball.blink_timer.unpause();
@@ -226,15 +364,176 @@ fn scored_ball_enter(
 
 fn scored_ball_update(
     mut ball_q: Query<&mut Ball>,
-    mut state: ResMut<State<GameState>>,
-    time: Res<Time>,
+    mut sim: ResMut<SimState>,
 ) {
     let mut ball = ball_q.single_mut();
-    ball.hold_timer.tick(time.delta());
+    ball.hold_timer.tick(Duration::from_secs_f32(TICK_SECONDS));
 
     if ball.hold_timer.finished() {
         ball.hold_timer.reset();
-        ball.hold_timer.pause();
-        state.set(GameState::Resetting).unwrap();
+        sim.0 = GameState::Resetting;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The ball hitting the left paddle's face `offset` from its middle.
+    fn hit_left_paddle(offset: f32, paddle_velocity: Velocity) -> CollisionEvent {
+        CollisionEvent {
+            entity: Entity::from_raw(0),
+            normal: Vec2::X,
+            other_velocity: paddle_velocity,
+            hit_offset: Some(offset),
+        }
+    }
+
+    fn still() -> Velocity {
+        Velocity { x: 0.0, y: 0.0 }
+    }
+
+    fn speed(velocity: &Velocity) -> f32 {
+        Vec2::new(velocity.x, velocity.y).length()
+    }
+
+    #[test]
+    fn rebounds_angle_further_from_the_middle() {
+        let rules = GameRules::default();
+        let incoming = Velocity { x: -300.0, y: 0.0 };
+
+        for offset in [-1.0, -0.5, 0.0, 0.25, 1.0] {
+            let collision = hit_left_paddle(offset, still());
+            let rebound = paddle_rebound(&incoming, &collision, offset, &rules);
+            let angle = rebound.y.atan2(rebound.x);
+
+            assert!(rebound.x > 0.0);
+            assert!(
+                (angle - offset * rules.max_bounce_angle).abs() < 1e-4,
+                "{} off the middle went off at {}",
+                offset,
+                angle.to_degrees()
+            );
+        }
+    }
+
+    #[test]
+    fn every_hit_speeds_the_ball_up() {
+        let rules = GameRules::default();
+        let mut velocity = Velocity { x: -300.0, y: 100.0 };
+        let mut expected = speed(&velocity);
+
+        for hit in 0..3 {
+            // Back and forth between the paddles, as in a rally.
+            let normal = if hit % 2 == 0 { Vec2::X } else { -Vec2::X };
+            let collision = CollisionEvent {
+                normal,
+                ..hit_left_paddle(0.3, still())
+            };
+            velocity = paddle_rebound(&velocity, &collision, 0.3, &rules);
+            expected *= rules.speed_up;
+
+            assert!((speed(&velocity) - expected).abs() < 1e-2, "hit {}", hit);
+        }
+    }
+
+    #[test]
+    fn rebounds_never_pass_the_top_speed() {
+        let rules = GameRules::default();
+        let incoming = Velocity { x: -(rules.max_ball_speed - 10.0), y: 0.0 };
+
+        let rebound = paddle_rebound(&incoming, &hit_left_paddle(0.0, still()), 0.0, &rules);
+        assert!((speed(&rebound) - rules.max_ball_speed).abs() < 1e-2);
+
+        // Not even with the paddle moving into it.
+        let paddle = Velocity { x: rules.push_speed, y: rules.paddle_speed };
+        let rebound = paddle_rebound(&incoming, &hit_left_paddle(0.8, paddle), 0.8, &rules);
+        assert!(speed(&rebound) <= rules.max_ball_speed + 1e-2);
+    }
+
+    #[test]
+    fn ball_bounces_more_than_once_in_a_tick() {
+        let mut app = App::new();
+        app.insert_resource(GameRules::default())
+            .add_event::<CollisionEvent>()
+            .add_system(ball_movement);
+        app.world
+            .spawn()
+            .insert(Court)
+            .insert(Transform::default())
+            .insert(BoundingBox { width: 40.0, height: 10.0 });
+        // Fast enough to cross the court twice over in one tick: up 4.5 to
+        // the top wall, down 9.0 to the bottom one and 6.5 back up.
+        let ball = app
+            .world
+            .spawn()
+            .insert(Ball {
+                hold_timer: Timer::from_seconds(1.0, false),
+                blink_timer: Timer::from_seconds(0.25, false),
+                reset_timer: Timer::from_seconds(2.0, false),
+            })
+            .insert(Velocity { x: 0.0, y: 20.0 / TICK_SECONDS })
+            .insert(Transform::default())
+            .insert(BoundingBox { width: 1.0, height: 1.0 })
+            .id();
+
+        app.update();
+
+        let events = app.world.resource::<Events<CollisionEvent>>();
+        let normals: Vec<_> = events.get_reader().iter(events).map(|event| event.normal).collect();
+        assert_eq!(normals, vec![-Vec2::Y, Vec2::Y]);
+
+        let y = app.world.get::<Transform>(ball).unwrap().translation.y;
+        assert!((y - 2.0).abs() < 1e-3, "ended at {}", y);
+        assert!(app.world.get::<Velocity>(ball).unwrap().y > 0.0);
+    }
+
+    /// Serves once, with the ball last heading at `x` speed and the court
+    /// centred on `court`.
+    fn serve(seed: u64, x: f32, court: Vec3) -> (Vec3, Velocity, GameState) {
+        let mut app = App::new();
+        app.insert_resource(GameRules::default())
+            .insert_resource(GameRng::new(seed))
+            .insert_resource(SimState(GameState::Serving))
+            .add_system(serve_ball);
+        app.world
+            .spawn()
+            .insert(Court)
+            .insert(Transform::from_translation(court));
+        let ball = app
+            .world
+            .spawn()
+            .insert(Ball {
+                hold_timer: Timer::from_seconds(1.0, false),
+                blink_timer: Timer::from_seconds(0.25, false),
+                reset_timer: Timer::from_seconds(2.0, false),
+            })
+            .insert(Velocity { x, y: 0.0 })
+            .insert(Transform::from_xyz(30.0, -20.0, 2.0))
+            .id();
+        app.world.spawn().insert(PaddleIntent {
+            serve: true,
+            ..default()
+        });
+
+        app.update();
+
+        let translation = app.world.get::<Transform>(ball).unwrap().translation;
+        let velocity = *app.world.get::<Velocity>(ball).unwrap();
+        (translation, velocity, app.world.resource::<SimState>().0.clone())
+    }
+
+    #[test]
+    fn serves_go_the_way_the_ball_last_went() {
+        let court = Vec3::new(0.0, -33.0, 1.0);
+        for seed in 0..20 {
+            let (translation, velocity, state) = serve(seed, -250.0, court);
+            assert!(velocity.x < 0.0, "seed {} served right", seed);
+            assert_eq!(translation, Vec3::new(0.0, -33.0, 2.0));
+            assert_eq!(state, GameState::Playing);
+
+            let (_, velocity, _) = serve(seed, 250.0, court);
+            assert!(velocity.x > 0.0, "seed {} served left", seed);
+        }
     }
 }
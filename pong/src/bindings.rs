@@ -0,0 +1,510 @@
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::prelude::*;
+
+const BINDINGS_PATH: &str = "controls.txt";
+
+/// Keys that can be bound. The rest are left alone, including the digits,
+/// `Enter` and `Escape`, which the game uses itself: `1` and `2` change who
+/// plays each side, the other digits and `Enter` jump through replays, and
+/// `Enter` also picks from menus and hands an unplugged gamepad's paddle to
+/// the keyboard.
+const BINDABLE_KEYS: &[KeyCode] = &[
+    KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G,
+    KeyCode::H, KeyCode::I, KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N,
+    KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R, KeyCode::S, KeyCode::T, KeyCode::U,
+    KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z,
+    KeyCode::Numpad0, KeyCode::Numpad1, KeyCode::Numpad2, KeyCode::Numpad3,
+    KeyCode::Numpad4, KeyCode::Numpad5, KeyCode::Numpad6, KeyCode::Numpad7,
+    KeyCode::Numpad8, KeyCode::Numpad9,
+    KeyCode::Up, KeyCode::Down, KeyCode::Left, KeyCode::Right,
+    KeyCode::Space, KeyCode::Tab, KeyCode::Back,
+    KeyCode::LShift, KeyCode::RShift, KeyCode::LControl, KeyCode::RControl,
+    KeyCode::LAlt, KeyCode::RAlt,
+    KeyCode::Insert, KeyCode::Delete, KeyCode::Home, KeyCode::End,
+    KeyCode::PageUp, KeyCode::PageDown,
+    KeyCode::Comma, KeyCode::Period, KeyCode::Slash, KeyCode::Semicolon,
+    KeyCode::Apostrophe, KeyCode::LBracket, KeyCode::RBracket, KeyCode::Minus,
+    KeyCode::Equals, KeyCode::Backslash,
+];
+
+/// Bindable keys the game reads too, outside of a match: the menus move
+/// with the arrows, and replays scrub and change speed with them and pause
+/// with `Space`. No paddle moves in either, so they don't clash, but binding
+/// one says where else it's used.
+const SHARED_KEYS: &[(KeyCode, &str)] = &[
+    (KeyCode::Up, "MENUS AND REPLAYS"),
+    (KeyCode::Down, "MENUS AND REPLAYS"),
+    (KeyCode::Left, "REPLAYS"),
+    (KeyCode::Right, "REPLAYS"),
+    (KeyCode::Space, "REPLAYS"),
+];
+
+/// Loads the key bindings from `controls.txt`, or the file given with
+/// `--controls <file>`. The menus' settings open a screen to change them,
+/// which saves them back there.
+pub struct BindingsPlugin;
+
+impl Plugin for BindingsPlugin {
+    fn build(&self, app: &mut App) {
+        let path = bindings_path_from_args();
+        let bindings = if path.exists() {
+            KeyBindings::load(&path).unwrap_or_else(|error| {
+                warn!("Couldn't load the controls from {}: {}", path.display(), error);
+                KeyBindings::default()
+            })
+        } else {
+            KeyBindings::default()
+        };
+
+        app.insert_resource(bindings)
+            .insert_resource(ControlsScreen::new(path))
+            .add_system(use_controls_screen)
+            .add_system(show_controls_screen.after(use_controls_screen));
+    }
+}
+
+fn bindings_path_from_args() -> PathBuf {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--controls" {
+            if let Some(path) = args.next() {
+                return PathBuf::from(path);
+            }
+        }
+    }
+    PathBuf::from(BINDINGS_PATH)
+}
+
+/// One paddle's keys.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PaddleKeys {
+    pub up: KeyCode,
+    pub down: KeyCode,
+    pub push_left: KeyCode,
+    pub push_right: KeyCode,
+}
+
+/// Which key does what, for both paddles. Serving is shared, as either side
+/// may serve.
+#[derive(Clone, PartialEq, Debug)]
+pub struct KeyBindings {
+    pub left: PaddleKeys,
+    pub right: PaddleKeys,
+    pub serve: KeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            left: PaddleKeys {
+                up: KeyCode::W,
+                down: KeyCode::S,
+                push_left: KeyCode::A,
+                push_right: KeyCode::D,
+            },
+            right: PaddleKeys {
+                up: KeyCode::Up,
+                down: KeyCode::Down,
+                push_left: KeyCode::Left,
+                push_right: KeyCode::Right,
+            },
+            serve: KeyCode::Space,
+        }
+    }
+}
+
+/// Anything a key can be bound to.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    Up(Player),
+    Down(Player),
+    PushLeft(Player),
+    PushRight(Player),
+    Serve,
+}
+
+impl Action {
+    const ALL: [Action; 9] = [
+        Action::Up(Player::Left),
+        Action::Down(Player::Left),
+        Action::PushLeft(Player::Left),
+        Action::PushRight(Player::Left),
+        Action::Up(Player::Right),
+        Action::Down(Player::Right),
+        Action::PushLeft(Player::Right),
+        Action::PushRight(Player::Right),
+        Action::Serve,
+    ];
+
+    /// As it's written in the file, such as `left_push_right`.
+    fn name(&self) -> String {
+        let (player, action) = match self {
+            Action::Up(player) => (player, "up"),
+            Action::Down(player) => (player, "down"),
+            Action::PushLeft(player) => (player, "push_left"),
+            Action::PushRight(player) => (player, "push_right"),
+            Action::Serve => return "serve".to_string(),
+        };
+        match player {
+            Player::Left => format!("left_{}", action),
+            Player::Right => format!("right_{}", action),
+        }
+    }
+
+    fn label(&self) -> String {
+        self.name().replace('_', " ").to_uppercase()
+    }
+}
+
+fn key_name(key: KeyCode) -> String {
+    format!("{:?}", key)
+}
+
+fn key_from_name(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS.iter().copied().find(|key| key_name(*key) == name)
+}
+
+impl KeyBindings {
+    pub fn load(path: &Path) -> Result<KeyBindings, String> {
+        fs::read_to_string(path)
+            .map_err(|error| error.to_string())?
+            .parse()
+    }
+
+    pub fn save(&self, path: &Path) {
+        match fs::write(path, self.to_string()) {
+            Ok(()) => info!("Saved the controls to {}", path.display()),
+            Err(error) => warn!("Couldn't save the controls to {}: {}", path.display(), error),
+        }
+    }
+
+    pub fn paddle(&self, player: Player) -> &PaddleKeys {
+        match player {
+            Player::Left => &self.left,
+            Player::Right => &self.right,
+        }
+    }
+
+    fn paddle_mut(&mut self, player: Player) -> &mut PaddleKeys {
+        match player {
+            Player::Left => &mut self.left,
+            Player::Right => &mut self.right,
+        }
+    }
+
+    pub fn key(&self, action: Action) -> KeyCode {
+        match action {
+            Action::Up(player) => self.paddle(player).up,
+            Action::Down(player) => self.paddle(player).down,
+            Action::PushLeft(player) => self.paddle(player).push_left,
+            Action::PushRight(player) => self.paddle(player).push_right,
+            Action::Serve => self.serve,
+        }
+    }
+
+    fn key_mut(&mut self, action: Action) -> &mut KeyCode {
+        match action {
+            Action::Up(player) => &mut self.paddle_mut(player).up,
+            Action::Down(player) => &mut self.paddle_mut(player).down,
+            Action::PushLeft(player) => &mut self.paddle_mut(player).push_left,
+            Action::PushRight(player) => &mut self.paddle_mut(player).push_right,
+            Action::Serve => &mut self.serve,
+        }
+    }
+
+    /// Another action already bound to `key`, if there is one.
+    pub fn conflict(&self, action: Action, key: KeyCode) -> Option<Action> {
+        Action::ALL
+            .iter()
+            .copied()
+            .find(|other| *other != action && self.key(*other) == key)
+    }
+}
+
+/// Saved as text: a `pong controls` header, then one line per action with
+/// its name and its key, such as `left_up W`.
+impl fmt::Display for KeyBindings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "pong controls")?;
+        for action in Action::ALL {
+            writeln!(f, "{} {}", action.name(), key_name(self.key(action)))?;
+        }
+        Ok(())
+    }
+}
+
+/// Actions the file leaves out keep their default keys.
+impl FromStr for KeyBindings {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().map(str::trim).filter(|line| !line.is_empty());
+        if lines.next() != Some("pong controls") {
+            return Err("not a pong controls file".to_string());
+        }
+
+        let mut bindings = KeyBindings::default();
+        for line in lines {
+            let (name, key) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| format!("expected an action and a key in `{}`", line))?;
+            let action = Action::ALL
+                .iter()
+                .copied()
+                .find(|action| action.name() == name)
+                .ok_or_else(|| format!("unknown action `{}`", name))?;
+            let key = key_from_name(key.trim()).ok_or_else(|| format!("can't bind `{}`", key))?;
+            *bindings.key_mut(action) = key;
+        }
+
+        for action in Action::ALL {
+            if let Some(other) = bindings.conflict(action, bindings.key(action)) {
+                return Err(format!(
+                    "{} is bound to both `{}` and `{}`",
+                    key_name(bindings.key(action)),
+                    action.name(),
+                    other.name()
+                ));
+            }
+        }
+        Ok(bindings)
+    }
+}
+
+/// The rebinding screen, and what's selected on it. It's only opened from a
+/// menu, which has already paused the game.
+pub struct ControlsScreen {
+    open: bool,
+    path: PathBuf,
+    selected: usize,
+    /// Waiting for the key to bind to the selected action.
+    waiting: bool,
+    message: String,
+}
+
+impl ControlsScreen {
+    fn new(path: PathBuf) -> Self {
+        ControlsScreen {
+            open: false,
+            path,
+            selected: 0,
+            waiting: false,
+            message: String::new(),
+        }
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+        self.waiting = false;
+        self.message.clear();
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+}
+
+/// `Up` and `Down` choose an action and `Enter` rebinds it to the next key
+/// pressed, unless that key is already taken. `R` resets every key to its
+/// default and `Escape` closes the screen.
+fn use_controls_screen(
+    keys: Res<Input<KeyCode>>,
+    mut screen: ResMut<ControlsScreen>,
+    mut bindings: ResMut<KeyBindings>,
+) {
+    if !screen.open {
+        return;
+    }
+
+    if screen.waiting {
+        let key = match keys.get_just_pressed().next() {
+            Some(key) => *key,
+            None => return,
+        };
+        let action = Action::ALL[screen.selected];
+        screen.waiting = false;
+
+        if key == KeyCode::Escape {
+            screen.message.clear();
+        } else if !BINDABLE_KEYS.contains(&key) {
+            screen.message = format!("{} CAN'T BE BOUND", key_name(key).to_uppercase());
+        } else if let Some(other) = bindings.conflict(action, key) {
+            screen.message = format!(
+                "{} IS ALREADY {}",
+                key_name(key).to_uppercase(),
+                other.label()
+            );
+        } else {
+            *bindings.key_mut(action) = key;
+            bindings.save(&screen.path);
+            screen.message = match SHARED_KEYS.iter().find(|(shared, _)| *shared == key) {
+                Some((_, used)) => {
+                    format!("{} IS ALSO USED IN {}", key_name(key).to_uppercase(), used)
+                }
+                None => String::new(),
+            };
+        }
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Escape) {
+        screen.open = false;
+    } else if keys.just_pressed(KeyCode::Up) {
+        screen.selected = screen.selected.checked_sub(1).unwrap_or(Action::ALL.len() - 1);
+    } else if keys.just_pressed(KeyCode::Down) {
+        screen.selected = (screen.selected + 1) % Action::ALL.len();
+    } else if keys.just_pressed(KeyCode::Return) {
+        screen.waiting = true;
+        screen.message = format!("PRESS A KEY FOR {}", Action::ALL[screen.selected].label());
+    } else if keys.just_pressed(KeyCode::R) {
+        *bindings = KeyBindings::default();
+        bindings.save(&screen.path);
+        screen.message = "BACK TO THE DEFAULTS".to_string();
+    }
+}
+
+#[derive(Component)]
+struct ControlsScreenNode;
+
+fn show_controls_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    screen: Res<ControlsScreen>,
+    bindings: Res<KeyBindings>,
+    node_q: Query<Entity, With<ControlsScreenNode>>,
+) {
+    if !screen.is_changed() && !bindings.is_changed() {
+        return;
+    }
+
+    for entity in node_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !screen.open {
+        return;
+    }
+
+    let style = TextStyle {
+        font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+        font_size: 32.0,
+        color: Color::WHITE,
+    };
+    let mut sections = vec![TextSection {
+        value: "CONTROLS\n\n".to_string(),
+        style: style.clone(),
+    }];
+    for (index, action) in Action::ALL.iter().enumerate() {
+        let color = if index == screen.selected {
+            Color::rgb(1.0, 0.8, 0.2)
+        } else {
+            Color::WHITE
+        };
+        sections.push(TextSection {
+            value: format!(
+                "{:<18}{:>10}\n",
+                action.label(),
+                key_name(bindings.key(*action)).to_uppercase()
+            ),
+            style: TextStyle {
+                color,
+                ..style.clone()
+            },
+        });
+    }
+    sections.push(TextSection {
+        value: format!(
+            "\n{}\nENTER rebind   R defaults   ESC close",
+            screen.message
+        ),
+        style: TextStyle {
+            font_size: 24.0,
+            color: Color::rgb(0.6, 0.6, 0.6),
+            ..style
+        },
+    });
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: UiColor::from(Color::rgba(0.1, 0.1, 0.1, 0.9)),
+            ..default()
+        })
+        .insert(ControlsScreenNode)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text {
+                    sections,
+                    alignment: TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        ..default()
+                    },
+                },
+                ..default()
+            });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_read_back_what_they_wrote() {
+        let mut bindings = KeyBindings::default();
+        bindings.left.up = KeyCode::Q;
+        bindings.right.push_left = KeyCode::Numpad4;
+        bindings.serve = KeyCode::LShift;
+
+        assert_eq!(bindings.to_string().parse(), Ok(bindings));
+    }
+
+    #[test]
+    fn missing_actions_keep_their_defaults() {
+        let bindings: KeyBindings = "pong controls\nleft_up I\n".parse().unwrap();
+
+        assert_eq!(bindings.left.up, KeyCode::I);
+        assert_eq!(bindings.left.down, KeyCode::S);
+        assert_eq!(bindings.serve, KeyCode::Space);
+    }
+
+    #[test]
+    fn files_with_mistakes_are_refused() {
+        for file in [
+            "left_up W",
+            "pong controls\nleft_jump W",
+            "pong controls\nleft_up",
+            "pong controls\nleft_up Escape",
+        ] {
+            assert!(file.parse::<KeyBindings>().is_err(), "accepted {:?}", file);
+        }
+    }
+
+    #[test]
+    fn keys_the_game_uses_itself_cant_be_bound() {
+        for key in ["Return", "Key1", "Key2", "Key5", "Escape"] {
+            let file = format!("pong controls\nserve {}", key);
+            assert!(file.parse::<KeyBindings>().is_err(), "bound {}", key);
+        }
+    }
+
+    #[test]
+    fn one_key_for_two_actions_is_a_conflict() {
+        let bindings = KeyBindings::default();
+
+        assert_eq!(bindings.conflict(Action::Serve, KeyCode::W), Some(Action::Up(Player::Left)));
+        assert_eq!(bindings.conflict(Action::Up(Player::Left), KeyCode::W), None);
+        assert_eq!(bindings.conflict(Action::Serve, KeyCode::P), None);
+        assert!("pong controls\nright_up W".parse::<KeyBindings>().is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use crate::prelude::*;
+
+/// Where a box moving along a displacement first touches something.
+#[derive(Clone, Copy, Debug)]
+pub struct Hit {
+    /// How far along the displacement the contact is, from 0.0 to 1.0.
+    pub time: f32,
+    /// The surface normal at the contact, pointing back towards the mover.
+    pub normal: Vec2,
+    /// The mover's centre at the contact.
+    pub position: Vec2,
+}
+
+/// Sweeps a box with `half_size` from `position` along `displacement`
+/// against a still box, by casting a ray against the target grown by the
+/// mover's size. A mover that starts out overlapping is pushed out along the
+/// shallowest axis and reported as a hit at time 0.0.
+pub fn sweep_aabb(
+    position: Vec2,
+    half_size: Vec2,
+    displacement: Vec2,
+    target: Vec2,
+    target_half_size: Vec2,
+) -> Option<Hit> {
+    let reach = half_size + target_half_size;
+    let offset = position - target;
+
+    let depth = reach - offset.abs();
+    if depth.x > 0.0 && depth.y > 0.0 {
+        let side = |value: f32| if value < 0.0 { -1.0 } else { 1.0 };
+        let (normal, push) = if depth.x < depth.y {
+            (Vec2::new(side(offset.x), 0.0), depth.x)
+        } else {
+            (Vec2::new(0.0, side(offset.y)), depth.y)
+        };
+        return Some(Hit {
+            time: 0.0,
+            normal,
+            position: position + normal * push,
+        });
+    }
+
+    let mut entry = f32::NEG_INFINITY;
+    let mut exit = f32::INFINITY;
+    let mut normal = Vec2::ZERO;
+    for axis in 0..2 {
+        let (offset, distance, reach) = (offset[axis], displacement[axis], reach[axis]);
+        if distance == 0.0 {
+            if offset.abs() >= reach {
+                return None;
+            }
+            continue;
+        }
+
+        let a = (-reach - offset) / distance;
+        let b = (reach - offset) / distance;
+        let (near, far) = (a.min(b), a.max(b));
+        if near > entry {
+            entry = near;
+            normal = Vec2::ZERO;
+            normal[axis] = -distance.signum();
+        }
+        exit = exit.min(far);
+    }
+
+    if entry >= exit || !(0.0..=1.0).contains(&entry) {
+        return None;
+    }
+
+    Some(Hit {
+        time: entry,
+        normal,
+        position: position + displacement * entry,
+    })
+}
+
+/// Sweeps a box with `half_size` along `displacement` against the top and
+/// bottom walls it's travelling between.
+pub fn sweep_walls(
+    position: Vec2,
+    half_size: Vec2,
+    displacement: Vec2,
+    bottom: f32,
+    top: f32,
+) -> Option<Hit> {
+    let (wall, normal) = if displacement.y > 0.0 {
+        (top - half_size.y, -Vec2::Y)
+    } else if displacement.y < 0.0 {
+        (bottom + half_size.y, Vec2::Y)
+    } else {
+        return None;
+    };
+
+    let time = ((wall - position.y) / displacement.y).max(0.0);
+    if time > 1.0 {
+        return None;
+    }
+
+    Some(Hit {
+        time,
+        normal,
+        position: Vec2::new(position.x + displacement.x * time, wall),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BALL: Vec2 = Vec2::new(0.5, 0.5);
+    const PADDLE: Vec2 = Vec2::new(0.5, 2.0);
+
+    fn assert_near(actual: Vec2, expected: Vec2) {
+        assert!((actual - expected).length() < 1e-5, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn fast_ball_hits_a_paddle_it_would_cross_in_one_tick() {
+        let hit = sweep_aabb(Vec2::new(-10.0, 0.0), BALL, Vec2::new(40.0, 0.0), Vec2::ZERO, PADDLE)
+            .expect("the ball should hit the paddle");
+
+        assert!((hit.time - 0.225).abs() < 1e-6);
+        assert_eq!(hit.normal, Vec2::new(-1.0, 0.0));
+        assert_near(hit.position, Vec2::new(-1.0, 0.0));
+    }
+
+    #[test]
+    fn ball_starting_inside_a_paddle_is_pushed_out_the_shallow_side() {
+        let hit = sweep_aabb(Vec2::new(-0.8, 1.0), BALL, Vec2::new(0.0, 3.0), Vec2::ZERO, PADDLE)
+            .expect("an overlapping ball should be reported");
+
+        assert_eq!(hit.time, 0.0);
+        assert_eq!(hit.normal, Vec2::new(-1.0, 0.0));
+        assert_near(hit.position, Vec2::new(-1.0, 1.0));
+    }
+
+    #[test]
+    fn corner_hits_bounce_off_the_face() {
+        // Heads straight for the paddle's bottom-left corner, touching both
+        // sides at once; ties go to the face so the paddle angles the ball.
+        let hit = sweep_aabb(Vec2::new(-3.0, -4.5), BALL, Vec2::new(4.0, 4.0), Vec2::ZERO, PADDLE)
+            .expect("the ball should hit the corner");
+
+        assert_eq!(hit.time, 0.5);
+        assert_eq!(hit.normal, Vec2::new(-1.0, 0.0));
+        assert_near(hit.position, Vec2::new(-1.0, -2.5));
+    }
+
+    #[test]
+    fn grazing_past_a_corner_misses() {
+        let hit = sweep_aabb(Vec2::new(-3.0, -4.5), BALL, Vec2::new(4.0, 2.0), Vec2::ZERO, PADDLE);
+
+        assert!(hit.is_none());
+    }
+
+    #[test]
+    fn fast_ball_stops_at_the_wall_it_would_cross() {
+        let hit = sweep_walls(Vec2::ZERO, BALL, Vec2::new(2.0, 20.0), -5.0, 5.0)
+            .expect("the ball should hit the top wall");
+
+        assert!((hit.time - 0.225).abs() < 1e-6);
+        assert_eq!(hit.normal, -Vec2::Y);
+        assert_near(hit.position, Vec2::new(0.45, 4.5));
+    }
+}
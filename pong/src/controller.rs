@@ -0,0 +1,200 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use bevy::ecs::system::SystemParam;
+use bevy::math::Vec3Swizzles;
+
+use crate::prelude::*;
+
+pub struct ControllerPlugin;
+
+impl Plugin for ControllerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(apply_controls)
+            // Every tick, as serving and rematches are asked for here too.
+            .add_system_to_stage(
+                PhysicsStage,
+                read_controllers
+                    .after(remember_positions)
+                    .before(paddle_control)
+            );
+    }
+}
+
+/// What a paddle wants to do this frame, whoever is driving it.
+#[derive(Component, Clone, Copy, Default, PartialEq, Debug)]
+pub struct PaddleIntent {
+    /// Up is positive, from -1.0 to 1.0 of full speed.
+    pub vertical: f32,
+    /// Push towards the right when positive, from -1.0 to 1.0.
+    pub push: f32,
+    /// Serve, or start a rematch once the match is over.
+    pub serve: bool,
+}
+
+impl PaddleIntent {
+    pub fn clamped(self) -> PaddleIntent {
+        PaddleIntent {
+            vertical: self.vertical.clamp(-1.0, 1.0),
+            push: self.push.clamp(-1.0, 1.0),
+            serve: self.serve,
+        }
+    }
+}
+
+/// Everything a controller may look at to decide on an intent.
+pub struct ControlContext<'a> {
+    pub player: Player,
+    pub keys: &'a Input<KeyCode>,
+    pub bindings: &'a KeyBindings,
+    pub buttons: &'a Input<GamepadButton>,
+    pub axes: &'a Axis<GamepadAxis>,
+    pub delta: Duration,
+    /// How far the paddle moves per second at full intent.
+    pub paddle_speed: f32,
+    pub paddle: Vec2,
+    pub paddle_size: Vec2,
+    pub ball: Vec2,
+    pub ball_velocity: Vec2,
+    pub ball_size: Vec2,
+    pub court: Vec2,
+    pub court_size: Vec2,
+}
+
+/// Anything that can drive a paddle: a keyboard, the AI, a script, ...
+pub trait PaddleController: Send + Sync + 'static {
+    fn intent(&mut self, context: &ControlContext) -> PaddleIntent;
+}
+
+#[derive(Component)]
+pub struct Controller(pub Box<dyn PaddleController>);
+
+impl Controller {
+    pub fn new(controller: impl PaddleController) -> Self {
+        Controller(Box::new(controller))
+    }
+
+    /// AIs are seeded from the match's seed rather than drawing from the
+    /// `GameRng`, so swapping controls mid-match never changes the serves.
+    pub fn for_control(control: Control, player: Player, rng: &GameRng) -> Self {
+        match control {
+            Control::Human => Controller::new(KeyboardController::for_player(player)),
+            Control::Ai(difficulty) => {
+                let seed = rng.seed().wrapping_add(player as u64);
+                Controller::new(AiController::new(difficulty, seed))
+            }
+            Control::Gamepad(gamepad) => Controller::new(GamepadController { gamepad }),
+        }
+    }
+}
+
+/// Drives a paddle with that side's keys from the `KeyBindings`, so
+/// rebinding them takes effect straight away.
+pub struct KeyboardController {
+    pub player: Player,
+}
+
+impl KeyboardController {
+    pub fn for_player(player: Player) -> Self {
+        KeyboardController { player }
+    }
+}
+
+impl PaddleController for KeyboardController {
+    fn intent(&mut self, context: &ControlContext) -> PaddleIntent {
+        let paddle_keys = context.bindings.paddle(self.player);
+        let axis = |positive: KeyCode, negative: KeyCode| {
+            if context.keys.pressed(positive) {
+                1.0
+            } else if context.keys.pressed(negative) {
+                -1.0
+            } else {
+                0.0
+            }
+        };
+
+        PaddleIntent {
+            vertical: axis(paddle_keys.up, paddle_keys.down),
+            push: axis(paddle_keys.push_right, paddle_keys.push_left),
+            serve: context.keys.pressed(context.bindings.serve),
+        }
+    }
+}
+
+/// Plays back a fixed list of intents, one per frame, then stands still.
+#[derive(Default)]
+pub struct ScriptedController {
+    pub intents: VecDeque<PaddleIntent>,
+}
+
+impl PaddleController for ScriptedController {
+    fn intent(&mut self, _context: &ControlContext) -> PaddleIntent {
+        self.intents.pop_front().unwrap_or_default()
+    }
+}
+
+/// Swaps in the matching controller when a paddle's `Control` is changed.
+pub fn apply_controls(
+    mut commands: Commands,
+    paddle_q: Query<(Entity, &Player, &Control), Changed<Control>>,
+    rng: Res<GameRng>,
+) {
+    for (entity, player, control) in paddle_q.iter() {
+        commands
+            .entity(entity)
+            .insert(Controller::for_control(*control, *player, &rng));
+    }
+}
+
+/// The ball and the court, as the controllers see them.
+#[derive(SystemParam)]
+pub struct BallAndCourt<'w, 's> {
+    ball_q: Query<
+        'w,
+        's,
+        (&'static Transform, &'static Velocity, &'static BoundingBox),
+        With<Ball>,
+    >,
+    court_q: Query<
+        'w,
+        's,
+        (&'static Transform, &'static BoundingBox),
+        (With<Court>, Without<Ball>, Without<Player>),
+    >,
+}
+
+pub fn read_controllers(
+    mut paddle_q: Query<
+        (&Player, &mut Controller, &mut PaddleIntent, &Transform, &BoundingBox),
+        Without<Ball>,
+    >,
+    ball_and_court: BallAndCourt,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    rules: Res<GameRules>,
+) {
+    let (ball_t, ball_v, ball_bbox) = ball_and_court.ball_q.single();
+    let (court_t, court_bbox) = ball_and_court.court_q.single();
+
+    for (player, mut controller, mut intent, transform, bbox) in paddle_q.iter_mut() {
+        let context = ControlContext {
+            player: *player,
+            keys: &keys,
+            bindings: &bindings,
+            buttons: &buttons,
+            axes: &axes,
+            delta: Duration::from_secs_f32(TICK_SECONDS),
+            paddle_speed: rules.paddle_speed,
+            paddle: transform.translation.xy(),
+            paddle_size: bbox.as_vec2(),
+            ball: ball_t.translation.xy(),
+            ball_velocity: Vec2::new(ball_v.x, ball_v.y),
+            ball_size: ball_bbox.as_vec2(),
+            court: court_t.translation.xy(),
+            court_size: court_bbox.as_vec2(),
+        };
+        *intent = controller.0.intent(&context).clamped();
+    }
+}
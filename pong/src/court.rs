@@ -5,16 +5,10 @@ pub struct CourtPlugin;
 impl Plugin for CourtPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system(setup_court)
-            .add_system_set(
-                SystemSet::on_update(GameState::Resetting)
-                    .with_system(court_collisions),
-            )
-            .add_system_set(
-                SystemSet::on_update(GameState::Serving)
-                    .with_system(court_collisions),
-            )
-            .add_system_set(
-                SystemSet::on_update(GameState::Playing)
+            .add_system_set_to_stage(
+                PhysicsStage,
+                SystemSet::new()
+                    .with_run_criteria(while_on_court)
                     .with_system(court_collisions.after(ball_movement)),
             );
     }
@@ -64,7 +58,7 @@ fn setup_court(mut commands: Commands, windows: Res<Windows>) {
         .insert(Court);
 }
 
-fn court_collisions(
+pub fn court_collisions(
     mut collision_event: EventWriter<CollisionEvent>,
     mut scored_event: EventWriter<ScoredEvent>,
     mut collidables_q: Query<
@@ -85,37 +79,38 @@ fn court_collisions(
         let adjusted_top = court_top - bbox.height / 2.0;
         let adjusted_bottom = court_bottom + bbox.height / 2.0;
 
-        let mut location = Vec2::ZERO;
+        // The ball finds the top and bottom walls itself in `ball_movement`,
+        // so only check whether it got past a paddle.
         if let Some(_ball) = opt_ball {
             if transform.translation.x > adjusted_right {
                 transform.translation.x = adjusted_right;
-                location.x = bbox.width / 2.0;
                 scored_event.send(ScoredEvent {
                     player: Player::Left,
                 });
             } else if transform.translation.x < adjusted_left {
                 transform.translation.x = adjusted_left;
-                location.x = -bbox.width / 2.0;
                 scored_event.send(ScoredEvent {
                     player: Player::Right,
                 });
             }
+            continue;
         }
 
-        if transform.translation.y >= adjusted_top {
+        let normal = if transform.translation.y >= adjusted_top {
             transform.translation.y = adjusted_top;
-            location.y = bbox.height / 2.0;
+            -Vec2::Y
         } else if transform.translation.y <= adjusted_bottom {
             transform.translation.y = adjusted_bottom;
-            location.y = -bbox.height / 2.0;
-        }
+            Vec2::Y
+        } else {
+            continue;
+        };
 
-        if location != Vec2::ZERO {
-            collision_event.send(CollisionEvent {
-                entity,
-                location,
-                other_velocity: Velocity { x: 0.0, y: 0.0 },
-            });
-        }
+        collision_event.send(CollisionEvent {
+            entity,
+            normal,
+            other_velocity: Velocity { x: 0.0, y: 0.0 },
+            hit_offset: None,
+        });
     }
 }
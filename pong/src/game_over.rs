@@ -0,0 +1,244 @@
+use std::env;
+
+use crate::prelude::*;
+
+pub struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MatchRules::from_args())
+            .init_resource::<Winner>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameOver)
+                    .with_system(spawn_winner_screen)
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::GameOver)
+                    .with_system(despawn_winner_screen)
+            )
+            .add_system_set_to_stage(
+                PhysicsStage,
+                SystemSet::new()
+                    .with_run_criteria(while_game_over)
+                    .with_system(rematch.after(read_controllers))
+            );
+    }
+}
+
+/// How a match is won: first to `winning_score`, optionally by two clear points.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchRules {
+    pub winning_score: isize,
+    pub win_by_two: bool,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        MatchRules {
+            winning_score: 11,
+            win_by_two: false,
+        }
+    }
+}
+
+impl MatchRules {
+    /// Reads `--first-to <n>` and `--win-by-two`, ignoring anything else.
+    pub fn from_args() -> Self {
+        let mut rules = MatchRules::default();
+        let mut args = env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--first-to" => {
+                    if let Some(score) = args.next().and_then(|value| value.parse().ok()) {
+                        rules.winning_score = isize::max(score, 1);
+                    }
+                }
+                "--win-by-two" => rules.win_by_two = true,
+                _ => {}
+            }
+        }
+
+        rules
+    }
+
+    pub fn winner(&self, left: isize, right: isize) -> Option<Player> {
+        let lead = if self.win_by_two { 2 } else { 1 };
+        if left >= self.winning_score && left - right >= lead {
+            Some(Player::Left)
+        } else if right >= self.winning_score && right - left >= lead {
+            Some(Player::Right)
+        } else {
+            None
+        }
+    }
+}
+
+/// Who won the last match, if it's over.
+#[derive(Default)]
+pub struct Winner(pub Option<Player>);
+
+#[derive(Component)]
+struct WinnerScreen;
+
+fn spawn_winner_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    winner: Res<Winner>,
+    score_q: Query<(&Score, &Player)>,
+) {
+    let winner = match winner.0 {
+        Some(Player::Left) => "LEFT",
+        Some(Player::Right) => "RIGHT",
+        None => return,
+    };
+    let mut scores = [0, 0];
+    for (score, player) in score_q.iter() {
+        match player {
+            Player::Left => scores[0] = score.0,
+            Player::Right => scores[1] = score.0,
+        }
+    }
+
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: UiColor::from(Color::rgba(0.1, 0.1, 0.1, 0.8)),
+            ..default()
+        })
+        .insert(WinnerScreen)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    format!(
+                        "{} PLAYER WINS {} - {}\nPress SPACE for a rematch",
+                        winner, scores[0], scores[1]
+                    ),
+                    TextStyle {
+                        font,
+                        font_size: 48.0,
+                        color: Color::WHITE,
+                    },
+                    TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        ..default()
+                    },
+                ),
+                ..default()
+            });
+        });
+}
+
+fn despawn_winner_screen(mut commands: Commands, screen_q: Query<Entity, With<WinnerScreen>>) {
+    for entity in screen_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Starts a new match from 0 - 0 when either side serves, sending the ball
+/// back to the middle.
+fn rematch(
+    intent_q: Query<&PaddleIntent>,
+    mut score_q: Query<&mut Score>,
+    mut scoreboard_q: Query<&mut Text, With<Scoreboard>>,
+    mut winner: ResMut<Winner>,
+    mut sim: ResMut<SimState>,
+    playback: Option<Res<Playback>>,
+) {
+    if playback.is_some() || !intent_q.iter().any(|intent| intent.serve) {
+        return;
+    }
+
+    for mut score in score_q.iter_mut() {
+        score.0 = 0;
+    }
+    update_scoreboard(&mut scoreboard_q.single_mut(), 0, 0);
+    winner.0 = None;
+    sim.0 = GameState::Resetting;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_to_eleven_wins() {
+        let rules = MatchRules::default();
+
+        assert_eq!(rules.winner(11, 9), Some(Player::Left));
+        assert_eq!(rules.winner(10, 11), Some(Player::Right));
+        assert_eq!(rules.winner(11, 10), Some(Player::Left));
+        assert_eq!(rules.winner(10, 10), None);
+    }
+
+    #[test]
+    fn win_by_two_plays_on_past_eleven() {
+        let rules = MatchRules {
+            win_by_two: true,
+            ..default()
+        };
+
+        assert_eq!(rules.winner(11, 9), Some(Player::Left));
+        assert_eq!(rules.winner(11, 10), None);
+        assert_eq!(rules.winner(11, 11), None);
+        assert_eq!(rules.winner(12, 11), None);
+        assert_eq!(rules.winner(12, 10), Some(Player::Left));
+        assert_eq!(rules.winner(13, 15), Some(Player::Right));
+    }
+
+    /// A finished 11 - 7 match, with the left paddle holding serve or not.
+    fn finished_match(serve: bool) -> App {
+        let mut app = App::new();
+        app.insert_resource(Winner(Some(Player::Left)))
+            .insert_resource(SimState(GameState::GameOver))
+            .add_system(rematch);
+        for (player, score, serve) in [(Player::Left, 11, serve), (Player::Right, 7, false)] {
+            app.world
+                .spawn()
+                .insert(player)
+                .insert(Score(score))
+                .insert(PaddleIntent { serve, ..default() });
+        }
+        app.world.spawn().insert(Scoreboard).insert(Text {
+            sections: vec![TextSection::default(); 4],
+            ..default()
+        });
+        app
+    }
+
+    fn scores(app: &mut App) -> Vec<isize> {
+        app.world.query::<&Score>().iter(&app.world).map(|score| score.0).collect()
+    }
+
+    #[test]
+    fn serving_after_the_match_starts_a_rematch() {
+        let mut app = finished_match(true);
+
+        app.update();
+
+        assert_eq!(scores(&mut app), vec![0, 0]);
+        assert_eq!(app.world.resource::<Winner>().0, None);
+        assert_eq!(app.world.resource::<SimState>().0, GameState::Resetting);
+        let scoreboard = app.world.query::<&Text>().single(&app.world);
+        assert_eq!(scoreboard.sections[0].value, "0");
+        assert_eq!(scoreboard.sections[2].value, "0");
+    }
+
+    #[test]
+    fn no_rematch_until_someone_serves() {
+        let mut app = finished_match(false);
+
+        app.update();
+
+        assert_eq!(scores(&mut app), vec![11, 7]);
+        assert_eq!(app.world.resource::<Winner>().0, Some(Player::Left));
+        assert_eq!(app.world.resource::<SimState>().0, GameState::GameOver);
+    }
+}
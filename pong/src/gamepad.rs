@@ -0,0 +1,234 @@
+use crate::prelude::*;
+
+/// How far the stick can lean before the paddle moves. Past it, the
+/// paddle's speed still starts from zero rather than jumping.
+const STICK_DEADZONE: f32 = 0.2;
+
+/// Pauses the game when a gamepad driving a paddle is unplugged, until it's
+/// plugged back in, `Start` is pressed on another gamepad to take over, or
+/// `Enter` hands the paddle back to the keyboard.
+pub struct GamepadPlugin;
+
+impl Plugin for GamepadPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MissingGamepads>()
+            .add_system(watch_gamepads)
+            .add_system(replace_missing_gamepads.after(watch_gamepads))
+            .add_system(show_missing_gamepads.after(replace_missing_gamepads));
+    }
+}
+
+/// Drives a paddle from a gamepad: the left stick or the d-pad to move, the
+/// shoulder buttons to push and `Start` to serve.
+pub struct GamepadController {
+    pub gamepad: Gamepad,
+}
+
+impl PaddleController for GamepadController {
+    fn intent(&mut self, context: &ControlContext) -> PaddleIntent {
+        let pressed = |button| context.buttons.pressed(GamepadButton(self.gamepad, button));
+        let stick = context
+            .axes
+            .get(GamepadAxis(self.gamepad, GamepadAxisType::LeftStickY))
+            .unwrap_or(0.0);
+
+        let vertical = if pressed(GamepadButtonType::DPadUp) {
+            1.0
+        } else if pressed(GamepadButtonType::DPadDown) {
+            -1.0
+        } else {
+            deadzone(stick)
+        };
+
+        // `LeftTrigger` and `RightTrigger` are the shoulder buttons; the
+        // triggers under them are `LeftTrigger2` and `RightTrigger2`.
+        let push = if pressed(GamepadButtonType::RightTrigger) {
+            1.0
+        } else if pressed(GamepadButtonType::LeftTrigger) {
+            -1.0
+        } else {
+            0.0
+        };
+
+        PaddleIntent {
+            vertical,
+            push,
+            serve: pressed(GamepadButtonType::Start),
+        }
+    }
+}
+
+/// Zero inside the deadzone, then rising from 0.0 to 1.0 over the rest of the
+/// stick's travel, keeping its sign.
+fn deadzone(value: f32) -> f32 {
+    if value.abs() <= STICK_DEADZONE {
+        0.0
+    } else {
+        value.signum() * (value.abs() - STICK_DEADZONE) / (1.0 - STICK_DEADZONE)
+    }
+}
+
+/// Sides whose gamepad has been unplugged, with the gamepad they're waiting
+/// for.
+#[derive(Default)]
+pub struct MissingGamepads(Vec<(Player, Gamepad)>);
+
+fn watch_gamepads(
+    mut events: EventReader<GamepadEvent>,
+    mut missing: ResMut<MissingGamepads>,
+    mut clock: ResMut<PhysicsClock>,
+    paddle_q: Query<(&Player, &Control)>,
+) {
+    for GamepadEvent(gamepad, event) in events.iter() {
+        match event {
+            GamepadEventType::Disconnected => {
+                for (player, control) in paddle_q.iter() {
+                    if *control == Control::Gamepad(*gamepad) {
+                        info!("Gamepad {} was unplugged", gamepad.0 + 1);
+                        missing.0.push((*player, *gamepad));
+                        clock.pause(PauseReason::Gamepad);
+                    }
+                }
+            }
+            GamepadEventType::Connected => {
+                missing.0.retain(|(_, missing)| missing != gamepad);
+                if missing.0.is_empty() {
+                    clock.resume(PauseReason::Gamepad);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `Start` on a gamepad that isn't driving a paddle takes over the first
+/// side missing one, and `Enter` gives every missing side to the keyboard.
+fn replace_missing_gamepads(
+    mut missing: ResMut<MissingGamepads>,
+    mut clock: ResMut<PhysicsClock>,
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    mut paddle_q: Query<(&Player, &mut Control)>,
+) {
+    if missing.0.is_empty() {
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Return) {
+        for (player, mut control) in paddle_q.iter_mut() {
+            if missing.0.iter().any(|(missing, _)| missing == player) {
+                *control = Control::Human;
+            }
+        }
+        missing.0.clear();
+    }
+
+    for gamepad in gamepads.iter() {
+        if !buttons.just_pressed(GamepadButton(*gamepad, GamepadButtonType::Start)) {
+            continue;
+        }
+        if paddle_q.iter().any(|(_, control)| *control == Control::Gamepad(*gamepad)) {
+            continue;
+        }
+        if missing.0.is_empty() {
+            break;
+        }
+
+        let (side, _) = missing.0.remove(0);
+        for (player, mut control) in paddle_q.iter_mut() {
+            if *player == side {
+                *control = Control::Gamepad(*gamepad);
+            }
+        }
+    }
+
+    if missing.0.is_empty() {
+        clock.resume(PauseReason::Gamepad);
+    }
+}
+
+#[derive(Component)]
+struct MissingGamepadScreen;
+
+fn show_missing_gamepads(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    missing: Res<MissingGamepads>,
+    screen_q: Query<Entity, With<MissingGamepadScreen>>,
+) {
+    if !missing.is_changed() {
+        return;
+    }
+
+    for entity in screen_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if missing.0.is_empty() {
+        return;
+    }
+
+    let mut message = String::new();
+    for (player, gamepad) in &missing.0 {
+        let side = match player {
+            Player::Left => "LEFT",
+            Player::Right => "RIGHT",
+        };
+        message.push_str(&format!("{} PLAYER'S GAMEPAD {} IS UNPLUGGED\n", side, gamepad.0 + 1));
+    }
+    message.push_str("Plug it back in, press START on another gamepad,\n");
+    message.push_str("or ENTER to use the keyboard");
+
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: UiColor::from(Color::rgba(0.1, 0.1, 0.1, 0.8)),
+            ..default()
+        })
+        .insert(MissingGamepadScreen)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    message,
+                    TextStyle {
+                        font,
+                        font_size: 36.0,
+                        color: Color::WHITE,
+                    },
+                    TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        ..default()
+                    },
+                ),
+                ..default()
+            });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_stick_leans_are_ignored() {
+        assert_eq!(deadzone(0.0), 0.0);
+        assert_eq!(deadzone(0.15), 0.0);
+        assert_eq!(deadzone(-STICK_DEADZONE), 0.0);
+    }
+
+    #[test]
+    fn speed_rises_from_zero_past_the_deadzone() {
+        assert!(deadzone(STICK_DEADZONE + 0.001) < 0.01);
+        assert!((deadzone(0.6) - 0.5).abs() < 1e-6);
+        assert!((deadzone(-0.6) + 0.5).abs() < 1e-6);
+        assert_eq!(deadzone(1.0), 1.0);
+        assert_eq!(deadzone(-1.0), -1.0);
+    }
+}
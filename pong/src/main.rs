@@ -1,18 +1,38 @@
 use rand::Rng;
 
-use bevy::{
-    prelude::*,
-    sprite::collide_aabb::{collide, Collision},
-    window::PresentMode,
-};
+use bevy::{prelude::*, window::PresentMode};
 
+use crate::ai::*;
 use crate::ball::*;
+use crate::bindings::*;
+use crate::collision::*;
+use crate::controller::*;
 use crate::court::*;
+use crate::game_over::*;
+use crate::gamepad::*;
+use crate::menu::*;
+use crate::netplay::*;
 use crate::paddles::*;
+use crate::physics::*;
+use crate::random::*;
+use crate::replay::*;
+use crate::rules::*;
 
+mod ai;
 mod ball;
+mod bindings;
+mod collision;
+mod controller;
 mod court;
+mod game_over;
+mod gamepad;
+mod menu;
+mod netplay;
 mod paddles;
+mod physics;
+mod random;
+mod replay;
+mod rules;
 mod prelude {
     pub use crate::*;
     pub use bevy::prelude::*;
@@ -23,17 +43,24 @@ pub const UI_HEIGHT: f32 = 66.0;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum GameState {
+    MainMenu,
+    /// Pushed on top of whichever state the match was in.
+    Paused,
     Resetting,
     Serving,
     Playing,
     Scored,
-    GameOver
+    GameOver,
 }
 
 pub struct CollisionEvent {
     entity: Entity,
-    location: Vec2,
+    /// The surface normal where `entity` was hit, pointing back at it.
+    normal: Vec2,
     other_velocity: Velocity,
+    /// Where a paddle's face was hit, from -1.0 at its bottom to 1.0 at its
+    /// top, or `None` for walls and the paddle's ends.
+    hit_offset: Option<f32>,
 }
 
 pub struct ScoredEvent {
@@ -67,14 +94,13 @@ pub struct Velocity {
 }
 
 impl Velocity {
-    pub fn random() -> Velocity {
-        let mut rng = rand::thread_rng();
-        let mut x = rng.gen_range(200.0..400.0);
+    pub fn random(rules: &GameRules, rng: &mut GameRng) -> Velocity {
+        let mut x = rng.gen_range(rules.serve_speed_x.clone());
         if rng.gen_bool(0.5) {
             x *= -1.0;
         }
 
-        let mut y = rng.gen_range(80.0..300.0);
+        let mut y = rng.gen_range(rules.serve_speed_y.clone());
         if rng.gen_bool(0.5) {
             y *= -1.0;
         }
@@ -90,6 +116,7 @@ fn main() {
     App::new()
         .insert_resource(Msaa { samples: 4 })
         .insert_resource(ClearColor(Color::rgb(0.1, 0.1, 0.1)))
+        .insert_resource(GameRules::default())
         .insert_resource(WindowDescriptor {
             title: "Pong".to_string(),
             present_mode: PresentMode::Fifo,
@@ -98,18 +125,23 @@ fn main() {
             ..default()
         })
         .add_plugins(DefaultPlugins)
+        .add_plugin(RandomPlugin)
+        .add_plugin(PhysicsPlugin)
         .add_plugin(BallPlugin)
         .add_plugin(CourtPlugin)
         .add_plugin(PlayerPlugin)
-        .add_state(GameState::Serving)
+        .add_plugin(GameOverPlugin)
+        .add_plugin(AiPlugin)
+        .add_plugin(BindingsPlugin)
+        .add_plugin(ControllerPlugin)
+        .add_plugin(GamepadPlugin)
+        .add_plugin(ReplayPlugin)
+        .add_plugin(NetplayPlugin)
+        // After the replay and netplay plugins, to know if they're in use.
+        .add_plugin(MenuPlugin)
         .add_startup_system(setup_camera)
         .add_event::<CollisionEvent>()
         .add_event::<ScoredEvent>()
-        .add_system_set(
-            SystemSet::on_update(GameState::Playing)
-                .with_system(paddle_control)
-                .with_system(paddle_ball_collisions.after(ball_movement)),
-        )
         .run();
 }
 
@@ -150,6 +182,14 @@ fn setup_camera(mut commands: Commands, asset_server: Res<AssetServer>) {
                             value: "0".to_string(),
                             style: text_style.clone(),
                         },
+                        // Status line, such as the replay controls.
+                        TextSection {
+                            value: String::new(),
+                            style: TextStyle {
+                                font_size: 24.0,
+                                ..text_style.clone()
+                            },
+                        },
                     ],
                     ..default()
                 },
@@ -157,62 +197,3 @@ fn setup_camera(mut commands: Commands, asset_server: Res<AssetServer>) {
             }).insert(Scoreboard);
         });
 }
-
-fn paddle_ball_collisions(
-    mut collision_event: EventWriter<CollisionEvent>,
-    mut ball_q: Query<(&mut Transform, &BoundingBox, Entity), With<Ball>>,
-    paddle_q: Query<(&Transform, &BoundingBox, &Velocity), (With<Player>, Without<Ball>)>,
-) {
-    let (mut ball_t, ball_bbox, ball_entity) = ball_q.single_mut();
-
-    for (paddle_t, paddle_bbox, paddle_v) in paddle_q.iter() {
-        if let Some(collision) = collide(
-            paddle_t.translation,
-            paddle_bbox.as_vec2(),
-            ball_t.translation,
-            ball_bbox.as_vec2(),
-        ) {
-            match collision {
-                Collision::Left => {
-                    ball_t.translation.x =
-                        paddle_t.translation.x + paddle_bbox.half_width() + ball_bbox.half_width();
-                    collision_event.send(CollisionEvent {
-                        entity: ball_entity,
-                        location: Vec2::new(-ball_bbox.half_width(), 0.0),
-                        other_velocity: *paddle_v,
-                    })
-                }
-                Collision::Right => {
-                    ball_t.translation.x =
-                        paddle_t.translation.x - paddle_bbox.half_width() - ball_bbox.half_width();
-                    collision_event.send(CollisionEvent {
-                        entity: ball_entity,
-                        location: Vec2::new(ball_bbox.half_width(), 0.0),
-                        other_velocity: *paddle_v,
-                    })
-                }
-                Collision::Top => {
-                    ball_t.translation.y = paddle_t.translation.y
-                        - paddle_bbox.half_height()
-                        - ball_bbox.half_height();
-                    collision_event.send(CollisionEvent {
-                        entity: ball_entity,
-                        location: Vec2::new(0.0, ball_bbox.half_height()),
-                        other_velocity: *paddle_v,
-                    })
-                }
-                Collision::Bottom => {
-                    ball_t.translation.y = paddle_t.translation.y
-                        + paddle_bbox.half_height()
-                        + ball_bbox.half_height();
-                    collision_event.send(CollisionEvent {
-                        entity: ball_entity,
-                        location: Vec2::new(0.0, -ball_bbox.half_height()),
-                        other_velocity: *paddle_v,
-                    })
-                }
-                Collision::Inside => {} // Shouldn't be possible...
-            }
-        }
-    }
-}
@@ -0,0 +1,573 @@
+use bevy::{app::AppExit, ecs::system::SystemParam, window::ReceivedCharacter};
+
+use crate::prelude::*;
+
+/// The title menu the game starts on, and the pause menu `Escape` opens
+/// over a match. Both stop the physics clock, which freezes the ball's
+/// timers and its reset animation along with everything else that steps on
+/// ticks; the blinking stops too, as `Paused` is pushed on top of the
+/// match's state rather than replacing it.
+///
+/// Replays and online matches started from the command line skip the title
+/// menu. Online matches can't be paused once connected, as the other side
+/// keeps playing, only before or after to go back to the title menu.
+///
+/// The title menu's own `ONLINE` hosts on `DEFAULT_PORT` or asks for an
+/// address to join.
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        let skip_title = app.world.contains_resource::<Playback>()
+            || app.world.contains_resource::<Netplay>();
+        let first_state = if skip_title {
+            GameState::Serving
+        } else {
+            GameState::MainMenu
+        };
+
+        app.add_state(first_state)
+            .init_resource::<Menu>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::MainMenu)
+                    .with_system(open_main_menu)
+                    .with_system(reset_match)
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::MainMenu)
+                    .with_system(close_menu)
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Paused)
+                    .with_system(open_pause_menu)
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Paused)
+                    .with_system(close_menu)
+            )
+            // After the controls screen, so the key that closes it doesn't
+            // reach the menu underneath too.
+            .add_system(toggle_pause.after(use_controls_screen))
+            .add_system(use_menu.after(use_controls_screen))
+            .add_system(show_menu.after(use_menu));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum MenuItem {
+    OnePlayer,
+    TwoPlayers,
+    Custom,
+    Online,
+    Host,
+    Join,
+    Settings,
+    Quit,
+    Resume,
+    MainMenu,
+    /// Cycles who moves that side's paddle.
+    Side(Player),
+    Play,
+    Back,
+}
+
+impl MenuItem {
+    fn label(&self, difficulty: Difficulty, controls: &[(Player, Control)]) -> String {
+        match self {
+            MenuItem::OnePlayer => format!("1P VS AI  < {} >", difficulty.label()),
+            MenuItem::TwoPlayers => "2P LOCAL".to_string(),
+            MenuItem::Custom => "CUSTOM".to_string(),
+            MenuItem::Online => "ONLINE".to_string(),
+            MenuItem::Host => format!("HOST ON PORT {}", DEFAULT_PORT),
+            MenuItem::Join => "JOIN".to_string(),
+            MenuItem::Settings => "SETTINGS".to_string(),
+            MenuItem::Quit => "QUIT".to_string(),
+            MenuItem::Resume => "RESUME".to_string(),
+            MenuItem::MainMenu => "MAIN MENU".to_string(),
+            MenuItem::Side(player) => {
+                let side = match player {
+                    Player::Left => "LEFT",
+                    Player::Right => "RIGHT",
+                };
+                let control = controls
+                    .iter()
+                    .find(|(other, _)| other == player)
+                    .map_or(Control::Human, |(_, control)| *control);
+                format!("{}: {}", side, control.label())
+            }
+            MenuItem::Play => "PLAY".to_string(),
+            MenuItem::Back => "BACK".to_string(),
+        }
+    }
+}
+
+/// The menu on screen, if any.
+#[derive(Default)]
+pub struct Menu {
+    title: &'static str,
+    items: Vec<MenuItem>,
+    selected: usize,
+    message: String,
+    /// The AI's difficulty for `1P VS AI`, kept from one menu to the next.
+    difficulty: Difficulty,
+    /// The address being typed in to join, once `JOIN` is picked.
+    address: Option<String>,
+}
+
+impl Menu {
+    fn open(&mut self, title: &'static str, items: Vec<MenuItem>) {
+        *self = Menu {
+            title,
+            items,
+            difficulty: self.difficulty,
+            ..default()
+        };
+    }
+}
+
+fn open_main_menu(mut menu: ResMut<Menu>, mut clock: ResMut<PhysicsClock>) {
+    menu.open("PONG", main_menu_items());
+    clock.pause(PauseReason::Menu);
+}
+
+fn main_menu_items() -> Vec<MenuItem> {
+    vec![
+        MenuItem::OnePlayer,
+        MenuItem::TwoPlayers,
+        MenuItem::Custom,
+        MenuItem::Online,
+        MenuItem::Settings,
+        MenuItem::Quit,
+    ]
+}
+
+fn open_pause_menu(mut menu: ResMut<Menu>, mut clock: ResMut<PhysicsClock>) {
+    menu.open(
+        "PAUSED",
+        vec![
+            MenuItem::Resume,
+            MenuItem::Settings,
+            MenuItem::MainMenu,
+            MenuItem::Quit,
+        ],
+    );
+    clock.pause(PauseReason::Menu);
+}
+
+/// Leaves the game paused if anything else paused it, such as a gamepad
+/// that's still unplugged.
+fn close_menu(mut menu: ResMut<Menu>, mut clock: ResMut<PhysicsClock>) {
+    menu.open("", Vec::new());
+    clock.resume(PauseReason::Menu);
+}
+
+/// The score and how far into the match it is.
+#[derive(SystemParam)]
+struct MatchProgress<'w, 's> {
+    score_q: Query<'w, 's, &'static mut Score>,
+    scoreboard_q: Query<'w, 's, &'static mut Text, With<Scoreboard>>,
+    winner: ResMut<'w, Winner>,
+    sim: ResMut<'w, SimState>,
+}
+
+/// Puts the court back as it was at the start, for the next match: the
+/// paddles still, halfway up their sides where they were spawned, and the
+/// ball in the middle with a new serve, waiting to be served.
+fn reset_match(
+    mut ball_q: Query<
+        (&mut Ball, &mut Transform, &mut Velocity, &mut Visibility),
+        (Without<Court>, Without<Player>),
+    >,
+    mut paddle_q: Query<
+        (&mut Transform, &mut Velocity, &mut PaddleIntent),
+        (With<Player>, Without<Ball>, Without<Court>),
+    >,
+    court_q: Query<&Transform, (With<Court>, Without<Ball>, Without<Player>)>,
+    rules: Res<GameRules>,
+    mut rng: ResMut<GameRng>,
+    mut progress: MatchProgress,
+) {
+    if let Ok(court) = court_q.get_single() {
+        if let Ok((mut ball, mut transform, mut velocity, mut visibility)) = ball_q.get_single_mut()
+        {
+            transform.translation.x = court.translation.x;
+            transform.translation.y = court.translation.y;
+            *velocity = Velocity::random(&rules, &mut rng);
+            ball.hold_timer.reset();
+            ball.reset_timer.reset();
+            ball.blink_timer.reset();
+            visibility.is_visible = true;
+        }
+
+        for (mut transform, mut velocity, mut intent) in paddle_q.iter_mut() {
+            transform.translation.y = court.translation.y;
+            *velocity = Velocity { x: 0.0, y: 0.0 };
+            *intent = PaddleIntent::default();
+        }
+    }
+
+    for mut score in progress.score_q.iter_mut() {
+        score.0 = 0;
+    }
+    if let Ok(mut scoreboard) = progress.scoreboard_q.get_single_mut() {
+        update_scoreboard(&mut scoreboard, 0, 0);
+    }
+    progress.winner.0 = None;
+    progress.sim.0 = GameState::Serving;
+}
+
+/// `Escape` pauses a match, and resumes it from the pause menu. It backs out
+/// of typing an address instead while that's going on.
+fn toggle_pause(
+    keys: Res<Input<KeyCode>>,
+    screen: Res<ControlsScreen>,
+    netplay: Option<Res<Netplay>>,
+    mut menu: ResMut<Menu>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if !keys.just_pressed(KeyCode::Escape) || screen.is_open() || screen.is_changed() {
+        return;
+    }
+    if menu.address.is_some() {
+        menu.address = None;
+        menu.message.clear();
+        return;
+    }
+
+    match state.current() {
+        GameState::MainMenu => {}
+        GameState::Paused => {
+            let _ = state.pop();
+        }
+        _ => {
+            if netplay.map_or(true, |netplay| !netplay.connected()) {
+                let _ = state.push(GameState::Paused);
+            }
+        }
+    }
+}
+
+/// The keys pressed and typed, and the gamepads there are to pick from.
+#[derive(SystemParam)]
+struct MenuInput<'w, 's> {
+    keys: Res<'w, Input<KeyCode>>,
+    typed: EventReader<'w, 's, ReceivedCharacter>,
+    gamepads: Res<'w, Gamepads>,
+}
+
+/// What picking a match from the menu sets up.
+#[derive(SystemParam)]
+struct MatchSetup<'w, 's> {
+    state: ResMut<'w, State<GameState>>,
+    clock: ResMut<'w, PhysicsClock>,
+    paddle_q: Query<'w, 's, (&'static Player, &'static mut Control)>,
+}
+
+/// `Up` and `Down` move through the menu and `Enter` picks. `Left` and
+/// `Right` change the AI's difficulty for `1P VS AI`. `CUSTOM` sets up each
+/// side, human, AI or gamepad, like `1` and `2` do while serving.
+fn use_menu(
+    mut commands: Commands,
+    input: MenuInput,
+    mut menu: ResMut<Menu>,
+    mut screen: ResMut<ControlsScreen>,
+    setup: MatchSetup,
+    mut exit: EventWriter<AppExit>,
+) {
+    let MenuInput { keys, mut typed, gamepads } = input;
+    let MatchSetup { mut state, mut clock, mut paddle_q } = setup;
+
+    // Read every frame, so nothing typed earlier turns up in the address.
+    let typed: String = typed.iter().map(|typed| typed.char).collect();
+    if menu.items.is_empty() || screen.is_open() || screen.is_changed() {
+        return;
+    }
+
+    if let Some(mut address) = menu.address.clone() {
+        address.extend(
+            typed
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | ':' | '-')),
+        );
+        if keys.just_pressed(KeyCode::Back) {
+            address.pop();
+        }
+
+        if keys.just_pressed(KeyCode::Return) {
+            match Netplay::join(&address) {
+                Ok(netplay) => {
+                    go_online(&mut commands, netplay, &mut clock, &mut paddle_q, &mut state)
+                }
+                Err(error) => menu.message = format!("Couldn't join: {}", error),
+            }
+        } else if menu.address.as_ref() != Some(&address) {
+            menu.message = format!("Address: {}_", address);
+            menu.address = Some(address);
+        }
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Up) {
+        menu.selected = menu.selected.checked_sub(1).unwrap_or(menu.items.len() - 1);
+    } else if keys.just_pressed(KeyCode::Down) {
+        menu.selected = (menu.selected + 1) % menu.items.len();
+    } else if menu.items[menu.selected] == MenuItem::OnePlayer {
+        if keys.just_pressed(KeyCode::Left) {
+            menu.difficulty = menu.difficulty.easier();
+        } else if keys.just_pressed(KeyCode::Right) {
+            menu.difficulty = menu.difficulty.harder();
+        }
+    }
+    if !keys.just_pressed(KeyCode::Return) {
+        return;
+    }
+
+    match menu.items[menu.selected] {
+        MenuItem::OnePlayer | MenuItem::TwoPlayers => {
+            let right = if menu.items[menu.selected] == MenuItem::OnePlayer {
+                Control::Ai(menu.difficulty)
+            } else {
+                Control::Human
+            };
+            for (player, mut control) in paddle_q.iter_mut() {
+                let wanted = match player {
+                    Player::Left => Control::Human,
+                    Player::Right => right,
+                };
+                if *control != wanted {
+                    *control = wanted;
+                }
+            }
+            let _ = state.set(GameState::Serving);
+        }
+        MenuItem::Custom => {
+            menu.open(
+                "CUSTOM",
+                vec![
+                    MenuItem::Side(Player::Left),
+                    MenuItem::Side(Player::Right),
+                    MenuItem::Play,
+                    MenuItem::Back,
+                ],
+            );
+        }
+        MenuItem::Side(side) => {
+            let controls: Vec<_> =
+                paddle_q.iter().map(|(player, control)| (*player, *control)).collect();
+            for (player, mut control) in paddle_q.iter_mut() {
+                if *player == side {
+                    *control = next_control(side, &controls, gamepads.iter().copied());
+                }
+            }
+        }
+        MenuItem::Play => {
+            let _ = state.set(GameState::Serving);
+        }
+        MenuItem::Back => menu.open("PONG", main_menu_items()),
+        MenuItem::Online => {
+            menu.open("ONLINE", vec![MenuItem::Host, MenuItem::Join, MenuItem::Back]);
+        }
+        MenuItem::Host => match Netplay::host(DEFAULT_PORT) {
+            Ok(netplay) => {
+                go_online(&mut commands, netplay, &mut clock, &mut paddle_q, &mut state)
+            }
+            Err(error) => menu.message = format!("Couldn't host: {}", error),
+        },
+        MenuItem::Join => {
+            menu.address = Some(String::new());
+            menu.message = "Address: _".to_string();
+        }
+        MenuItem::Settings => screen.open(),
+        MenuItem::Quit => exit.send(AppExit),
+        MenuItem::Resume => {
+            let _ = state.pop();
+        }
+        MenuItem::MainMenu => {
+            let _ = state.replace(GameState::MainMenu);
+        }
+    }
+}
+
+/// Starts an online match, paused until the other side is there. Each
+/// machine plays its paddle from the keyboard.
+fn go_online(
+    commands: &mut Commands,
+    netplay: Netplay,
+    clock: &mut PhysicsClock,
+    paddle_q: &mut Query<(&Player, &mut Control)>,
+    state: &mut State<GameState>,
+) {
+    commands.insert_resource(netplay);
+    clock.pause(PauseReason::Netplay);
+    for (_, mut control) in paddle_q.iter_mut() {
+        if *control != Control::Human {
+            *control = Control::Human;
+        }
+    }
+    let _ = state.set(GameState::Serving);
+}
+
+#[derive(Component)]
+struct MenuScreen;
+
+fn show_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    menu: Res<Menu>,
+    screen: Res<ControlsScreen>,
+    menu_q: Query<Entity, With<MenuScreen>>,
+    paddle_q: Query<(&Player, ChangeTrackers<Control>, &Control)>,
+) {
+    let controls_changed = paddle_q.iter().any(|(_, tracker, _)| tracker.is_changed());
+    if !menu.is_changed() && !screen.is_changed() && !controls_changed {
+        return;
+    }
+
+    for entity in menu_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    // The controls screen goes in its place while it's open.
+    if menu.items.is_empty() || screen.is_open() {
+        return;
+    }
+
+    let controls: Vec<_> =
+        paddle_q.iter().map(|(player, _, control)| (*player, *control)).collect();
+    let style = TextStyle {
+        font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+        font_size: 36.0,
+        color: Color::WHITE,
+    };
+    let mut sections = vec![TextSection {
+        value: format!("{}\n\n", menu.title),
+        style: TextStyle {
+            font_size: 72.0,
+            ..style.clone()
+        },
+    }];
+    for (index, item) in menu.items.iter().enumerate() {
+        let color = if index == menu.selected {
+            Color::rgb(1.0, 0.8, 0.2)
+        } else {
+            Color::WHITE
+        };
+        sections.push(TextSection {
+            value: format!("{}\n", item.label(menu.difficulty, &controls)),
+            style: TextStyle {
+                color,
+                ..style.clone()
+            },
+        });
+    }
+    sections.push(TextSection {
+        value: format!("\n{}", menu.message),
+        style: TextStyle {
+            font_size: 24.0,
+            color: Color::rgb(0.6, 0.6, 0.6),
+            ..style
+        },
+    });
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: UiColor::from(Color::rgba(0.1, 0.1, 0.1, 0.8)),
+            ..default()
+        })
+        .insert(MenuScreen)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text {
+                    sections,
+                    alignment: TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        ..default()
+                    },
+                },
+                ..default()
+            });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn the_next_match_starts_from_where_the_first_did() {
+        let mut app = App::new();
+        app.insert_resource(GameRules::default())
+            .insert_resource(GameRng::new(3))
+            .insert_resource(Winner(Some(Player::Right)))
+            .insert_resource(SimState(GameState::GameOver))
+            .add_system(reset_match);
+        app.world
+            .spawn()
+            .insert(Court)
+            .insert(Transform::from_xyz(0.0, -33.0, 1.0));
+        let mut blink_timer = Timer::from_seconds(0.08, false);
+        blink_timer.tick(Duration::from_secs_f32(0.05));
+        let ball = app
+            .world
+            .spawn()
+            .insert(Ball {
+                hold_timer: Timer::from_seconds(1.0, false),
+                blink_timer,
+                reset_timer: Timer::from_seconds(2.0, false),
+            })
+            .insert(Velocity { x: 900.0, y: -40.0 })
+            .insert(Visibility { is_visible: false })
+            .insert(Transform::from_xyz(410.0, 120.0, 2.0))
+            .id();
+        let paddles: Vec<Entity> = [(Player::Left, -350.0, 150.0), (Player::Right, 350.0, -90.0)]
+            .into_iter()
+            .map(|(player, x, y)| {
+                app.world
+                    .spawn()
+                    .insert(player)
+                    .insert(Score(if player == Player::Right { 11 } else { 4 }))
+                    .insert(PaddleIntent {
+                        vertical: 1.0,
+                        push: -1.0,
+                        serve: true,
+                    })
+                    .insert(Velocity { x: -133.0, y: 300.0 })
+                    .insert(Transform::from_xyz(x, y, 2.0))
+                    .id()
+            })
+            .collect();
+        app.world.spawn().insert(Scoreboard).insert(Text {
+            sections: vec![TextSection::default(); 4],
+            ..default()
+        });
+
+        app.update();
+
+        let transform = app.world.get::<Transform>(ball).unwrap();
+        assert_eq!(transform.translation, Vec3::new(0.0, -33.0, 2.0));
+        let velocity = app.world.get::<Velocity>(ball).unwrap();
+        assert!(GameRules::default().serve_speed_x.contains(&velocity.x.abs()));
+        let blink_timer = &app.world.get::<Ball>(ball).unwrap().blink_timer;
+        assert_eq!(blink_timer.elapsed_secs(), 0.0);
+        assert!(app.world.get::<Visibility>(ball).unwrap().is_visible);
+
+        for (paddle, x) in paddles.into_iter().zip([-350.0, 350.0]) {
+            let transform = app.world.get::<Transform>(paddle).unwrap();
+            assert_eq!(transform.translation, Vec3::new(x, -33.0, 2.0));
+            let velocity = app.world.get::<Velocity>(paddle).unwrap();
+            assert_eq!((velocity.x, velocity.y), (0.0, 0.0));
+            assert_eq!(*app.world.get::<PaddleIntent>(paddle).unwrap(), PaddleIntent::default());
+            assert_eq!(app.world.get::<Score>(paddle).unwrap().0, 0);
+        }
+        assert_eq!(app.world.resource::<Winner>().0, None);
+        assert_eq!(app.world.resource::<SimState>().0, GameState::Serving);
+    }
+}
@@ -0,0 +1,1118 @@
+use std::{
+    collections::VecDeque,
+    env,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    process,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use bevy::ecs::schedule::ShouldRun;
+use bevy::ecs::system::SystemParam;
+use rand::Rng;
+
+use crate::prelude::*;
+
+/// Ticks between pressing a key and the paddle moving, when
+/// `--input-delay` isn't given. It gives the other side's inputs that long
+/// to arrive before they'd have to be guessed.
+const DEFAULT_INPUT_DELAY: usize = 2;
+/// How many ticks ahead of the other side's last known input the simulation
+/// may guess before it waits for it.
+const MAX_PREDICTION: usize = 12;
+/// Snapshots kept to roll back to, which must cover `MAX_PREDICTION`.
+const MAX_SNAPSHOTS: usize = 120;
+/// Most inputs sent in one packet, however far behind the other side is.
+const MAX_INPUTS_PER_PACKET: usize = 64;
+/// How long without a packet before the other side counts as gone.
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// Marks every packet, so anything else arriving on the port is ignored.
+const MAGIC: &[u8; 4] = b"PONG";
+/// The port hosted on from the menu, and joined when none is typed.
+pub const DEFAULT_PORT: u16 = 7777;
+
+/// Two players on different machines over UDP. `--host <port>` waits for
+/// someone to `--join <address:port>`; the host plays the left paddle and
+/// the joiner the right, each with the controls the left or right player
+/// would have.
+///
+/// Both sides run the whole simulation. Each sends its inputs every frame
+/// and uses them itself `--input-delay <ticks>` later; the host's delay is
+/// the one both sides play with, as they must agree on it. When the other
+/// side's input for a tick hasn't arrived yet, its last one is assumed; if
+/// the real one turns out different, the simulation rolls back to the
+/// snapshot taken before that tick and plays forward again. Both of these
+/// are done by the paddles' controllers while online: a `DelayedController`
+/// on ours and a `RemoteController` on theirs.
+///
+/// `--latency <ms>` holds back every packet sent and `--packet-loss <0-1>`
+/// drops that share of them, to try bad connections with two copies of the
+/// game on one machine:
+///
+/// ```text
+/// pong --host 7777
+/// pong --join 127.0.0.1:7777 --latency 80 --packet-loss 0.1
+/// ```
+///
+/// The title menu's `ONLINE` hosts or joins the same way, without the
+/// made-up latency and packet loss. Going back to the title menu hangs up.
+pub struct NetplayPlugin;
+
+impl Plugin for NetplayPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(args) = NetplayArgs::from_args() {
+            match Netplay::new(args) {
+                Ok(netplay) => app.insert_resource(netplay),
+                Err(error) => {
+                    eprintln!("Couldn't open the connection: {}", error);
+                    process::exit(1);
+                }
+            };
+        }
+
+        app.add_system_set(
+            SystemSet::new()
+                .with_run_criteria(while_online)
+                .with_system(receive_messages)
+                .with_system(start_and_roll_back.after(receive_messages))
+                .with_system(take_over_controllers.after(apply_controls))
+                .with_system(show_netplay_status)
+        )
+        .add_system_set(
+            SystemSet::on_enter(GameState::MainMenu)
+                .with_system(hang_up)
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .with_run_criteria(while_online)
+                .with_system(send_messages)
+        )
+        .add_system_set_to_stage(
+            PhysicsStage,
+            SystemSet::new()
+                .with_run_criteria(while_online)
+                .with_system(save_snapshot.after(remember_positions).before(read_controllers))
+                .with_system(next_tick.after(read_controllers).before(paddle_control))
+        );
+    }
+}
+
+fn while_online(netplay: Option<Res<Netplay>>) -> ShouldRun {
+    if netplay.is_some() {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+/// Whichever side of a match this was, so the next one starts offline.
+fn hang_up(
+    mut commands: Commands,
+    netplay: Option<Res<Netplay>>,
+    mut clock: ResMut<PhysicsClock>,
+    mut control_q: Query<&mut Control>,
+    mut scoreboard_q: Query<&mut Text, With<Scoreboard>>,
+) {
+    if netplay.is_none() {
+        return;
+    }
+
+    commands.remove_resource::<Netplay>();
+    clock.resume(PauseReason::Netplay);
+    // Has `apply_controls` put back the controllers they had offline.
+    for mut control in control_q.iter_mut() {
+        control.set_changed();
+    }
+    if let Ok(mut scoreboard) = scoreboard_q.get_single_mut() {
+        scoreboard.sections[3].value.clear();
+    }
+}
+
+enum Role {
+    Host { port: u16 },
+    Join { address: SocketAddr },
+}
+
+struct NetplayArgs {
+    role: Role,
+    input_delay: usize,
+    latency: Duration,
+    packet_loss: f64,
+}
+
+impl NetplayArgs {
+    fn new(role: Role) -> Self {
+        NetplayArgs {
+            role,
+            input_delay: DEFAULT_INPUT_DELAY,
+            latency: Duration::ZERO,
+            packet_loss: 0.0,
+        }
+    }
+
+    /// Reads `--host`, `--join`, `--input-delay`, `--latency` and
+    /// `--packet-loss`, or `None` without `--host` or `--join`.
+    fn from_args() -> Option<Self> {
+        let mut role = None;
+        let mut input_delay = DEFAULT_INPUT_DELAY;
+        let mut latency = Duration::ZERO;
+        let mut packet_loss = 0.0;
+        let mut args = env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--host" => {
+                    if let Some(port) = args.next().and_then(|value| value.parse().ok()) {
+                        role = Some(Role::Host { port });
+                    }
+                }
+                "--join" => {
+                    if let Some(address) = args.next().as_deref().and_then(resolve) {
+                        role = Some(Role::Join { address });
+                    }
+                }
+                "--input-delay" => {
+                    if let Some(ticks) = args.next().and_then(|value| value.parse().ok()) {
+                        input_delay = ticks;
+                    }
+                }
+                "--latency" => {
+                    if let Some(ms) = args.next().and_then(|value| value.parse().ok()) {
+                        latency = Duration::from_millis(ms);
+                    }
+                }
+                "--packet-loss" => {
+                    if let Some(share) = args.next().and_then(|value| value.parse::<f64>().ok()) {
+                        packet_loss = share.clamp(0.0, 1.0);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Some(NetplayArgs {
+            role: role?,
+            input_delay,
+            latency,
+            packet_loss,
+        })
+    }
+}
+
+/// Looks up `address`, on `DEFAULT_PORT` if it doesn't say which.
+fn resolve(address: &str) -> Option<SocketAddr> {
+    let with_port;
+    let address = if address.contains(':') {
+        address
+    } else {
+        with_port = format!("{}:{}", address, DEFAULT_PORT);
+        &with_port
+    };
+
+    address
+        .to_socket_addrs()
+        .ok()?
+        // The socket is bound for IPv4, whatever `localhost` resolves to first.
+        .find(SocketAddr::is_ipv4)
+}
+
+/// A non-blocking UDP socket that can make the connection worse on purpose.
+struct FaultySocket {
+    socket: UdpSocket,
+    latency: Duration,
+    packet_loss: f64,
+    /// Packets held back, with when they're due to go out.
+    queued: VecDeque<(Instant, SocketAddr, Vec<u8>)>,
+}
+
+impl FaultySocket {
+    fn bind(port: u16, latency: Duration, packet_loss: f64) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        socket.set_nonblocking(true)?;
+        Ok(FaultySocket {
+            socket,
+            latency,
+            packet_loss,
+            queued: VecDeque::new(),
+        })
+    }
+
+    fn send(&mut self, to: SocketAddr, packet: Vec<u8>) {
+        // Not the `GameRng`: the other side mustn't have to agree on this.
+        if rand::thread_rng().gen_bool(self.packet_loss) {
+            return;
+        }
+        self.queued.push_back((Instant::now() + self.latency, to, packet));
+        self.flush();
+    }
+
+    /// Sends whatever has been held back long enough.
+    fn flush(&mut self) {
+        let now = Instant::now();
+        while let Some((due, to, packet)) = self.queued.front() {
+            if *due > now {
+                break;
+            }
+            // Lost like any other packet if it can't go out.
+            let _ = self.socket.send_to(packet, to);
+            self.queued.pop_front();
+        }
+    }
+
+    fn receive(&mut self) -> Vec<(SocketAddr, Vec<u8>)> {
+        let mut packets = Vec::new();
+        let mut buffer = [0; 2048];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((length, from)) => packets.push((from, buffer[..length].to_vec())),
+                // Nothing left, or something like the other side's port
+                // having closed; either way, try again next frame.
+                Err(_) => break,
+            }
+        }
+        packets
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Message {
+    /// From the joiner, until the host answers.
+    Hello,
+    /// The host's answer, with everything both sides must start from.
+    Welcome {
+        seed: u64,
+        match_rules: MatchRules,
+        input_delay: usize,
+    },
+    /// The sender's inputs from tick `first` on, and how many of the
+    /// receiver's inputs it has so far.
+    Inputs {
+        received: usize,
+        first: usize,
+        intents: Vec<PaddleIntent>,
+    },
+}
+
+impl Message {
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        match self {
+            Message::Hello => bytes.push(0),
+            Message::Welcome {
+                seed,
+                match_rules,
+                input_delay,
+            } => {
+                bytes.push(1);
+                bytes.extend_from_slice(&seed.to_le_bytes());
+                bytes.extend_from_slice(&(match_rules.winning_score as i64).to_le_bytes());
+                bytes.push(match_rules.win_by_two as u8);
+                bytes.extend_from_slice(&(*input_delay as u32).to_le_bytes());
+            }
+            Message::Inputs {
+                received,
+                first,
+                intents,
+            } => {
+                bytes.push(2);
+                bytes.extend_from_slice(&(*received as u32).to_le_bytes());
+                bytes.extend_from_slice(&(*first as u32).to_le_bytes());
+                for intent in intents {
+                    bytes.extend_from_slice(&intent.vertical.to_le_bytes());
+                    bytes.extend_from_slice(&intent.push.to_le_bytes());
+                    bytes.push(intent.serve as u8);
+                }
+            }
+        }
+        bytes
+    }
+
+    /// `None` for anything that isn't a whole message.
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader(bytes.strip_prefix(MAGIC)?);
+
+        match reader.byte()? {
+            0 => Some(Message::Hello),
+            1 => Some(Message::Welcome {
+                seed: u64::from_le_bytes(reader.take()?),
+                match_rules: MatchRules {
+                    winning_score: i64::from_le_bytes(reader.take()?) as isize,
+                    win_by_two: reader.byte()? != 0,
+                },
+                input_delay: u32::from_le_bytes(reader.take()?) as usize,
+            }),
+            2 => {
+                let received = u32::from_le_bytes(reader.take()?) as usize;
+                let first = u32::from_le_bytes(reader.take()?) as usize;
+                let mut intents = Vec::new();
+                while !reader.0.is_empty() {
+                    intents.push(PaddleIntent {
+                        vertical: f32::from_le_bytes(reader.take()?),
+                        push: f32::from_le_bytes(reader.take()?),
+                        serve: reader.byte()? != 0,
+                    });
+                }
+                Some(Message::Inputs {
+                    received,
+                    first,
+                    intents,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Takes bytes off the front of a packet.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        if self.0.len() < N {
+            return None;
+        }
+        let (taken, rest) = self.0.split_at(N);
+        self.0 = rest;
+        taken.try_into().ok()
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        self.take::<1>().map(|[byte]| byte)
+    }
+}
+
+#[derive(PartialEq)]
+enum Connection {
+    /// Hosting, and nobody has joined yet.
+    Waiting,
+    /// Joining, and the host hasn't answered yet.
+    Connecting,
+    /// Playing.
+    Connected,
+    /// Nothing heard from the other side for `TIMEOUT`.
+    Lost,
+    /// A wrong guess went back further than the snapshots kept, so the two
+    /// sides can't agree on the match any more.
+    Desynced,
+}
+
+/// Everything that changes from tick to tick, to roll back to.
+#[derive(Clone)]
+struct SimSnapshot {
+    ball: (Ball, Transform, Velocity),
+    paddles: Vec<(Player, Transform, Velocity, Score)>,
+    state: GameState,
+    rng: GameRng,
+    winner: Option<Player>,
+}
+
+/// Both sides' inputs by tick. The paddles' controllers read from it while
+/// the ticks are simulated, so it's shared with them.
+struct InputLog {
+    /// The next tick to simulate.
+    tick: usize,
+    /// One past the newest tick ever simulated. `tick` is only behind it
+    /// while ticks rolled back are being simulated again.
+    newest: usize,
+    /// Ours by tick, reaching the input delay past `newest`.
+    local_intents: Vec<PaddleIntent>,
+    /// Theirs by tick, as they arrive.
+    remote_intents: Vec<Option<PaddleIntent>>,
+    /// Theirs by tick as they were simulated, whether known or guessed.
+    used_intents: Vec<PaddleIntent>,
+    /// How many of their inputs have arrived without gaps.
+    remote_received: usize,
+    /// The earliest tick simulated with a wrong guess since the last frame.
+    rollback_to: Option<usize>,
+}
+
+impl InputLog {
+    /// Both sides stand still for the first `input_delay` ticks, as nothing
+    /// pressed can reach them any sooner.
+    fn new(input_delay: usize) -> Self {
+        InputLog {
+            tick: 0,
+            newest: 0,
+            local_intents: vec![PaddleIntent::default(); input_delay],
+            remote_intents: vec![Some(PaddleIntent::default()); input_delay],
+            used_intents: Vec::new(),
+            remote_received: input_delay,
+            rollback_to: None,
+        }
+    }
+
+    /// Their input for `tick`, or their last known one before it when it
+    /// hasn't arrived.
+    fn remote_intent(&self, tick: usize) -> PaddleIntent {
+        let known = &self.remote_intents[..self.remote_intents.len().min(tick + 1)];
+        known.iter().rev().find_map(|intent| *intent).unwrap_or_default()
+    }
+
+    fn receive_intents(&mut self, first: usize, intents: Vec<PaddleIntent>) {
+        // Nothing honest is that far ahead, and it would all be kept.
+        let limit = self.remote_received + MAX_SNAPSHOTS;
+        for (tick, intent) in (first..limit).zip(intents) {
+            if self.remote_intents.len() <= tick {
+                self.remote_intents.resize(tick + 1, None);
+            }
+            if self.remote_intents[tick].is_some() {
+                continue;
+            }
+            self.remote_intents[tick] = Some(intent);
+
+            if tick < self.newest && self.used_intents[tick] != intent {
+                self.rollback_to = Some(self.rollback_to.map_or(tick, |to| to.min(tick)));
+            }
+        }
+
+        while let Some(Some(_)) = self.remote_intents.get(self.remote_received) {
+            self.remote_received += 1;
+        }
+    }
+}
+
+/// Drives our paddle while online: asks `inner`, whatever would drive it
+/// offline, once for every new tick, and plays its answer the input delay
+/// later. Ticks simulated again after a rollback get what was asked the
+/// first time, so an AI's reaction timer and RNG only move on with new ticks.
+pub struct DelayedController {
+    inner: Controller,
+    inputs: Arc<Mutex<InputLog>>,
+}
+
+impl PaddleController for DelayedController {
+    fn intent(&mut self, context: &ControlContext) -> PaddleIntent {
+        let mut inputs = self.inputs.lock().unwrap();
+        if inputs.tick == inputs.newest {
+            let intent = self.inner.0.intent(context).clamped();
+            inputs.local_intents.push(intent);
+        }
+        inputs.local_intents[inputs.tick]
+    }
+}
+
+/// Drives their paddle with what they sent for each tick, or a guess at it
+/// until that arrives, keeping track of which so a wrong guess can be found.
+pub struct RemoteController {
+    inputs: Arc<Mutex<InputLog>>,
+}
+
+impl PaddleController for RemoteController {
+    fn intent(&mut self, _context: &ControlContext) -> PaddleIntent {
+        let mut inputs = self.inputs.lock().unwrap();
+        let tick = inputs.tick;
+        let intent = inputs.remote_intent(tick);
+        if tick < inputs.used_intents.len() {
+            inputs.used_intents[tick] = intent;
+        } else {
+            inputs.used_intents.push(intent);
+        }
+        intent
+    }
+}
+
+pub struct Netplay {
+    socket: FaultySocket,
+    connection: Connection,
+    /// Who to send to; for the host, not known until someone says hello.
+    peer: Option<SocketAddr>,
+    local: Player,
+    input_delay: usize,
+    /// The seed to start the match from, once connected.
+    start_seed: Option<u64>,
+    last_heard: Instant,
+    inputs: Arc<Mutex<InputLog>>,
+    /// How many of ours they've said they have.
+    acked: usize,
+    snapshots: VecDeque<(usize, SimSnapshot)>,
+    rollbacks: usize,
+}
+
+impl Netplay {
+    fn new(args: NetplayArgs) -> std::io::Result<Self> {
+        let (port, peer, local, connection) = match args.role {
+            Role::Host { port } => (port, None, Player::Left, Connection::Waiting),
+            Role::Join { address } => (0, Some(address), Player::Right, Connection::Connecting),
+        };
+
+        Ok(Netplay {
+            socket: FaultySocket::bind(port, args.latency, args.packet_loss)?,
+            connection,
+            peer,
+            local,
+            input_delay: args.input_delay,
+            start_seed: None,
+            last_heard: Instant::now(),
+            inputs: Arc::new(Mutex::new(InputLog::new(args.input_delay))),
+            acked: 0,
+            snapshots: VecDeque::new(),
+            rollbacks: 0,
+        })
+    }
+
+    /// Waits on `port` for someone to join, to play the left paddle.
+    pub fn host(port: u16) -> Result<Self, String> {
+        Netplay::new(NetplayArgs::new(Role::Host { port })).map_err(|error| error.to_string())
+    }
+
+    /// Joins whoever is hosting at `address`, to play the right paddle.
+    pub fn join(address: &str) -> Result<Self, String> {
+        let address = resolve(address).ok_or_else(|| format!("can't find {}", address))?;
+        Netplay::new(NetplayArgs::new(Role::Join { address })).map_err(|error| error.to_string())
+    }
+
+    pub fn connected(&self) -> bool {
+        self.connection == Connection::Connected
+    }
+
+    fn inputs(&self) -> MutexGuard<'_, InputLog> {
+        self.inputs.lock().unwrap()
+    }
+
+    /// What drives `player`'s paddle while online: `offline`, the input delay
+    /// late, for ours, and what they send for theirs.
+    pub fn controller(&self, player: Player, offline: Controller) -> Controller {
+        let inputs = Arc::clone(&self.inputs);
+        if player == self.local {
+            Controller::new(DelayedController {
+                inner: offline,
+                inputs,
+            })
+        } else {
+            Controller::new(RemoteController { inputs })
+        }
+    }
+}
+
+fn receive_messages(
+    mut netplay: ResMut<Netplay>,
+    mut match_rules: ResMut<MatchRules>,
+    rng: Res<GameRng>,
+) {
+    netplay.socket.flush();
+
+    for (from, packet) in netplay.socket.receive() {
+        let message = match Message::decode(&packet) {
+            Some(message) => message,
+            None => continue,
+        };
+        if netplay.peer.map_or(false, |peer| peer != from) {
+            continue;
+        }
+        netplay.last_heard = Instant::now();
+
+        match message {
+            Message::Hello => {
+                if netplay.local != Player::Left {
+                    continue;
+                }
+                // Answered every time, in case the last answer was lost.
+                let welcome = Message::Welcome {
+                    seed: rng.seed(),
+                    match_rules: match_rules.clone(),
+                    input_delay: netplay.input_delay,
+                };
+                netplay.socket.send(from, welcome.encode());
+                if netplay.connection == Connection::Waiting {
+                    netplay.peer = Some(from);
+                    netplay.connection = Connection::Connected;
+                    netplay.start_seed = Some(rng.seed());
+                }
+            }
+            Message::Welcome {
+                seed,
+                match_rules: host_rules,
+                input_delay,
+            } => {
+                if netplay.connection == Connection::Connecting {
+                    *match_rules = host_rules;
+                    netplay.connection = Connection::Connected;
+                    netplay.start_seed = Some(seed);
+                    // Nothing has been simulated yet, so nothing is lost.
+                    netplay.input_delay = input_delay;
+                    *netplay.inputs() = InputLog::new(input_delay);
+                }
+            }
+            Message::Inputs {
+                received,
+                first,
+                intents,
+            } => {
+                netplay.acked = netplay.acked.max(received);
+                netplay.inputs().receive_intents(first, intents);
+            }
+        }
+    }
+
+    if netplay.connected() && netplay.last_heard.elapsed() > TIMEOUT {
+        netplay.connection = Connection::Lost;
+    }
+}
+
+/// The ball, the paddles and the scoreboard, as a rollback restores them.
+#[derive(SystemParam)]
+struct CourtState<'w, 's> {
+    ball_q: Query<
+        'w,
+        's,
+        (&'static mut Ball, &'static mut Transform, &'static mut Velocity),
+        Without<Player>,
+    >,
+    paddle_q: Query<
+        'w,
+        's,
+        (&'static Player, &'static mut Transform, &'static mut Velocity, &'static mut Score),
+        Without<Ball>,
+    >,
+    scoreboard_q: Query<'w, 's, &'static mut Text, With<Scoreboard>>,
+}
+
+/// Starts the match on the frame the connection is made, and rolls back to
+/// before the earliest tick that was simulated with a wrong guess; the ticks
+/// since then run again this frame with the inputs that really happened.
+/// Holds the simulation while the other side is too far behind.
+fn start_and_roll_back(
+    mut netplay: ResMut<Netplay>,
+    mut clock: ResMut<PhysicsClock>,
+    mut rng: ResMut<GameRng>,
+    rules: Res<GameRules>,
+    mut sim: ResMut<SimState>,
+    mut winner: ResMut<Winner>,
+    mut court: CourtState,
+) {
+    if let Some(seed) = netplay.start_seed.take() {
+        // The court hasn't moved while waiting, so only the serve differs.
+        *rng = GameRng::new(seed);
+        let (_, _, mut velocity) = court.ball_q.single_mut();
+        *velocity = Velocity::random(&rules, &mut rng);
+    }
+
+    let rollback_to = netplay.inputs().rollback_to.take();
+    if let Some(tick) = rollback_to {
+        let snapshot = netplay
+            .snapshots
+            .iter()
+            .find(|(saved, _)| *saved == tick)
+            .map(|(_, snapshot)| snapshot.clone());
+
+        if snapshot.is_none() {
+            error!("No snapshot to roll back to tick {}; the match is out of sync", tick);
+            netplay.connection = Connection::Desynced;
+        }
+        if let Some(snapshot) = snapshot {
+            let (mut ball, mut transform, mut velocity) = court.ball_q.single_mut();
+            *ball = snapshot.ball.0;
+            *transform = snapshot.ball.1;
+            *velocity = snapshot.ball.2;
+
+            let mut scores = [0, 0];
+            for (player, mut transform, mut velocity, mut score) in court.paddle_q.iter_mut() {
+                let saved = snapshot.paddles.iter().find(|(saved, ..)| saved == player);
+                if let Some((_, saved_transform, saved_velocity, saved_score)) = saved {
+                    *transform = *saved_transform;
+                    *velocity = *saved_velocity;
+                    *score = *saved_score;
+                }
+                match player {
+                    Player::Left => scores[0] = score.0,
+                    Player::Right => scores[1] = score.0,
+                }
+            }
+            update_scoreboard(&mut court.scoreboard_q.single_mut(), scores[0], scores[1]);
+
+            sim.0 = snapshot.state;
+            *rng = snapshot.rng;
+            winner.0 = snapshot.winner;
+            netplay.rollbacks += 1;
+            let mut inputs = netplay.inputs();
+            clock.fast_forward(inputs.tick - tick);
+            inputs.tick = tick;
+        }
+    }
+
+    let inputs = netplay.inputs();
+    let waiting = !netplay.connected() || inputs.tick >= inputs.remote_received + MAX_PREDICTION;
+    clock.set_paused(PauseReason::Netplay, waiting);
+}
+
+/// Puts our paddle's controller behind the input delay and has theirs play
+/// what they send, on going online and whenever a paddle's control changes.
+fn take_over_controllers(
+    mut commands: Commands,
+    netplay: Res<Netplay>,
+    paddle_q: Query<(Entity, &Player, &Control, ChangeTrackers<Control>)>,
+    rng: Res<GameRng>,
+) {
+    for (entity, player, control, control_tracker) in paddle_q.iter() {
+        if netplay.is_added() || control_tracker.is_changed() {
+            let offline = Controller::for_control(*control, *player, &rng);
+            commands.entity(entity).insert(netplay.controller(*player, offline));
+        }
+    }
+}
+
+/// Saves the state before every tick, to roll back to.
+fn save_snapshot(
+    mut netplay: ResMut<Netplay>,
+    ball_q: Query<(&Ball, &Transform, &Velocity), Without<Player>>,
+    paddle_q: Query<(&Player, &Transform, &Velocity, &Score), Without<Ball>>,
+    sim: Res<SimState>,
+    rng: Res<GameRng>,
+    winner: Res<Winner>,
+) {
+    let (ball, transform, velocity) = ball_q.single();
+    let snapshot = SimSnapshot {
+        ball: (ball.clone(), *transform, *velocity),
+        paddles: paddle_q
+            .iter()
+            .map(|(player, transform, velocity, score)| (*player, *transform, *velocity, *score))
+            .collect(),
+        state: sim.0.clone(),
+        rng: rng.clone(),
+        winner: winner.0,
+    };
+
+    // Ticks being simulated again replace what was saved for them before.
+    let tick = netplay.inputs().tick;
+    while netplay.snapshots.back().map_or(false, |(saved, _)| *saved >= tick) {
+        netplay.snapshots.pop_back();
+    }
+    netplay.snapshots.push_back((tick, snapshot));
+    if netplay.snapshots.len() > MAX_SNAPSHOTS {
+        netplay.snapshots.pop_front();
+    }
+}
+
+/// Moves on once both paddles' controllers have had their say for the tick.
+fn next_tick(netplay: Res<Netplay>) {
+    let mut inputs = netplay.inputs();
+    if inputs.tick == inputs.newest {
+        inputs.newest += 1;
+    }
+    inputs.tick += 1;
+}
+
+/// Sends every input the other side hasn't said it has, every frame, so
+/// whatever a lost packet held goes out again with the next one.
+fn send_messages(mut netplay: ResMut<Netplay>) {
+    let peer = match netplay.peer {
+        Some(peer) => peer,
+        None => return,
+    };
+
+    let message = match netplay.connection {
+        Connection::Connecting => Message::Hello,
+        Connection::Connected => {
+            let inputs = netplay.inputs();
+            let first = netplay.acked.min(inputs.local_intents.len());
+            let last = inputs.local_intents.len().min(first + MAX_INPUTS_PER_PACKET);
+            Message::Inputs {
+                received: inputs.remote_received,
+                first,
+                intents: inputs.local_intents[first..last].to_vec(),
+            }
+        }
+        Connection::Waiting | Connection::Lost | Connection::Desynced => return,
+    };
+    netplay.socket.send(peer, message.encode());
+}
+
+fn show_netplay_status(
+    netplay: Res<Netplay>,
+    clock: Res<PhysicsClock>,
+    mut scoreboard_q: Query<&mut Text, With<Scoreboard>>,
+) {
+    let status = match netplay.connection {
+        Connection::Waiting => {
+            let port = netplay.socket.socket.local_addr().map_or(0, |address| address.port());
+            format!("\nWAITING FOR A PLAYER ON PORT {}", port)
+        }
+        Connection::Connecting => match netplay.peer {
+            Some(peer) => format!("\nCONNECTING TO {}", peer),
+            None => "\nCONNECTING".to_string(),
+        },
+        Connection::Connected if clock.is_paused_by(PauseReason::Netplay) => {
+            "\nWAITING FOR THE OTHER PLAYER".to_string()
+        }
+        Connection::Connected => format!(
+            "\nONLINE  DELAY {}  ROLLBACKS {}",
+            netplay.input_delay, netplay.rollbacks
+        ),
+        Connection::Lost => "\nCONNECTION LOST".to_string(),
+        Connection::Desynced => "\nOUT OF SYNC".to_string(),
+    };
+
+    let mut scoreboard = scoreboard_q.single_mut();
+    if scoreboard.sections[3].value != status {
+        scoreboard.sections[3].value = status;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT_DELAY: usize = 2;
+    const TICKS: usize = 150;
+
+    fn hosting() -> Netplay {
+        let args = NetplayArgs {
+            role: Role::Host { port: 0 },
+            input_delay: INPUT_DELAY,
+            latency: Duration::ZERO,
+            packet_loss: 0.0,
+        };
+        let mut netplay = Netplay::new(args).expect("couldn't bind a socket");
+        netplay.connection = Connection::Connected;
+        netplay
+    }
+
+    fn intent(vertical: f32) -> PaddleIntent {
+        PaddleIntent {
+            vertical,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn messages_decode_to_what_was_encoded() {
+        let messages = [
+            Message::Hello,
+            Message::Welcome {
+                seed: u64::MAX - 3,
+                match_rules: MatchRules {
+                    winning_score: 21,
+                    win_by_two: true,
+                },
+                input_delay: 6,
+            },
+            Message::Inputs {
+                received: 7,
+                first: 300,
+                intents: vec![
+                    intent(0.25),
+                    PaddleIntent {
+                        vertical: -1.0,
+                        push: 1.0,
+                        serve: true,
+                    },
+                ],
+            },
+        ];
+
+        for message in messages {
+            assert_eq!(Message::decode(&message.encode()), Some(message));
+        }
+    }
+
+    #[test]
+    fn cut_off_and_foreign_packets_are_ignored() {
+        let packet = Message::Welcome {
+            seed: 1,
+            match_rules: MatchRules::default(),
+            input_delay: INPUT_DELAY,
+        }
+        .encode();
+
+        assert_eq!(Message::decode(&packet[..packet.len() - 1]), None);
+        assert_eq!(Message::decode(b"PING\0"), None);
+    }
+
+    /// Just the messages going back and forth, under `args`.
+    fn online(args: NetplayArgs) -> App {
+        let mut app = App::new();
+        app.insert_resource(MatchRules::default())
+            .insert_resource(GameRng::new(7))
+            .insert_resource(Netplay::new(args).expect("couldn't bind a socket"))
+            .add_system(receive_messages)
+            .add_system(send_messages.after(receive_messages));
+        app
+    }
+
+    #[test]
+    fn joiners_play_with_the_hosts_input_delay() {
+        let mut host = online(NetplayArgs {
+            input_delay: 2,
+            ..NetplayArgs::new(Role::Host { port: 0 })
+        });
+        let port = host.world.resource::<Netplay>().socket.socket.local_addr().unwrap().port();
+        let address = resolve(&format!("127.0.0.1:{}", port)).unwrap();
+        let mut joiner = online(NetplayArgs {
+            input_delay: 5,
+            ..NetplayArgs::new(Role::Join { address })
+        });
+
+        for _ in 0..100 {
+            joiner.update();
+            host.update();
+            if joiner.world.resource::<Netplay>().connected() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        let netplay = joiner.world.resource::<Netplay>();
+        assert!(netplay.connected());
+        assert_eq!(netplay.input_delay, 2);
+        let inputs = netplay.inputs();
+        assert_eq!(inputs.local_intents.len(), 2);
+        assert_eq!(inputs.remote_received, 2);
+    }
+
+    #[test]
+    fn wrong_guesses_roll_back_to_the_first_one() {
+        let mut inputs = InputLog::new(INPUT_DELAY);
+        // Five ticks simulated, guessing they kept still.
+        inputs.newest = 5;
+        inputs.used_intents = vec![PaddleIntent::default(); 5];
+
+        inputs.receive_intents(INPUT_DELAY, vec![intent(0.0), intent(1.0), intent(1.0)]);
+
+        assert_eq!(inputs.rollback_to, Some(3));
+        assert_eq!(inputs.remote_received, 5);
+    }
+
+    #[test]
+    fn right_guesses_and_future_inputs_dont_roll_back() {
+        let mut inputs = InputLog::new(INPUT_DELAY);
+        inputs.newest = 4;
+        inputs.used_intents = vec![PaddleIntent::default(); 4];
+
+        inputs.receive_intents(INPUT_DELAY, vec![intent(0.0), intent(0.0), intent(1.0)]);
+
+        assert_eq!(inputs.rollback_to, None);
+        assert_eq!(inputs.remote_received, 5);
+    }
+
+    /// What we press on each tick, which the paddle follows `INPUT_DELAY`
+    /// ticks later.
+    fn local_intent(tick: usize) -> PaddleIntent {
+        intent(if tick < 40 { 1.0 } else { -0.5 })
+    }
+
+    /// Every input they sent, from the start of the match.
+    fn remote_intents() -> Vec<PaddleIntent> {
+        (0..TICKS + INPUT_DELAY)
+            .map(|tick| match tick {
+                _ if tick < INPUT_DELAY => PaddleIntent::default(),
+                _ if tick < 30 => intent(0.0),
+                _ if tick < 90 => intent(1.0),
+                _ => intent(-1.0),
+            })
+            .collect()
+    }
+
+    /// A court with the ball heading for the left paddle, and only what a
+    /// tick needs to move the paddles and the ball and to roll back. Our
+    /// paddle is scripted to press `local_intent`, which it's only asked for
+    /// once per tick: asked again on ticks simulated after a rollback, it
+    /// would fall behind the script.
+    fn court() -> App {
+        let mut app = App::new();
+        app.insert_resource(Time::default())
+            .insert_resource(GameRules::default())
+            .insert_resource(GameRng::new(7))
+            .init_resource::<Winner>()
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<KeyBindings>()
+            .init_resource::<Input<GamepadButton>>()
+            .init_resource::<Axis<GamepadAxis>>()
+            .insert_resource(hosting())
+            .add_event::<CollisionEvent>()
+            .add_state(GameState::Playing)
+            .add_plugin(PhysicsPlugin)
+            .insert_resource(SimState(GameState::Playing))
+            .add_system(start_and_roll_back)
+            .add_system_to_stage(PhysicsStage, save_snapshot.after(remember_positions))
+            .add_system_to_stage(PhysicsStage, read_controllers.after(save_snapshot))
+            .add_system_to_stage(PhysicsStage, next_tick.after(read_controllers))
+            .add_system_to_stage(PhysicsStage, paddle_control.after(next_tick))
+            .add_system_to_stage(PhysicsStage, ball_movement.after(paddle_control));
+
+        app.world
+            .spawn()
+            .insert(Court)
+            .insert(Transform::default())
+            .insert(BoundingBox { width: 800.0, height: 400.0 });
+        app.world
+            .spawn()
+            .insert(Ball {
+                hold_timer: Timer::from_seconds(1.0, false),
+                blink_timer: Timer::from_seconds(0.25, false),
+                reset_timer: Timer::from_seconds(2.0, false),
+            })
+            .insert(Velocity { x: -400.0, y: 150.0 })
+            .insert(Transform::default())
+            .insert(BoundingBox { width: 10.0, height: 10.0 });
+        for (player, x) in [(Player::Left, -350.0), (Player::Right, 350.0)] {
+            let script = ScriptedController {
+                intents: (0..TICKS).map(local_intent).collect(),
+            };
+            let controller = app
+                .world
+                .resource::<Netplay>()
+                .controller(player, Controller::new(script));
+            app.world
+                .spawn()
+                .insert(player)
+                .insert(Score(0))
+                .insert(controller)
+                .insert(PaddleIntent::default())
+                .insert(Velocity { x: 0.0, y: 0.0 })
+                .insert(Transform::from_xyz(x, 0.0, 0.0))
+                .insert(BoundingBox { width: 20.0, height: 100.0 });
+        }
+        app.world.spawn().insert(Scoreboard).insert(Text {
+            sections: vec![TextSection::default(); 4],
+            ..default()
+        });
+        app
+    }
+
+    fn receive(app: &mut App, intents: Vec<PaddleIntent>) {
+        app.world.resource::<Netplay>().inputs().receive_intents(0, intents);
+    }
+
+    /// Runs one more tick.
+    fn tick(app: &mut App) {
+        app.world.resource_mut::<PhysicsClock>().fast_forward(1);
+        app.update();
+    }
+
+    fn positions(app: &mut App) -> Vec<(Vec3, f32, f32)> {
+        app.world
+            .query::<(&Transform, &Velocity)>()
+            .iter(&app.world)
+            .map(|(transform, velocity)| (transform.translation, velocity.x, velocity.y))
+            .collect()
+    }
+
+    #[test]
+    fn rolling_back_ends_up_where_knowing_all_along_would() {
+        let remote = remote_intents();
+
+        let mut known = court();
+        for _ in 0..TICKS {
+            receive(&mut known, remote.clone());
+            tick(&mut known);
+        }
+        known.update();
+
+        // Their inputs arrive eight ticks late, so every change in them is
+        // guessed wrong and rolled back.
+        let mut guessed = court();
+        for t in 0..TICKS {
+            receive(&mut guessed, remote[..t.saturating_sub(8)].to_vec());
+            tick(&mut guessed);
+        }
+        receive(&mut guessed, remote);
+        guessed.update();
+
+        let netplay = guessed.world.resource::<Netplay>();
+        assert!(netplay.rollbacks > 0);
+        assert!(netplay.connected());
+        assert_eq!(netplay.inputs().tick, TICKS);
+        assert_eq!(positions(&mut guessed), positions(&mut known));
+    }
+}
@@ -7,74 +7,51 @@ pub struct PlayerPlugin;
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system(setup_paddles)
-            .add_system_set(
-                SystemSet::on_update(GameState::Serving)
-                    .with_system(paddle_control)
-            )
-            .add_system_set(
-                SystemSet::on_update(GameState::Resetting)
-                    .with_system(player_scored)
-                    .with_system(paddle_control)
+            .add_system_set_to_stage(
+                PhysicsStage,
+                SystemSet::new()
+                    .with_run_criteria(while_playing)
+                    .with_system(player_scored.after(court_collisions))
             )
-            .add_system_set(
-                SystemSet::on_update(GameState::Playing)
-                    .with_system(player_scored)
+            .add_system_set_to_stage(
+                PhysicsStage,
+                SystemSet::new()
+                    .with_run_criteria(while_on_court)
                     .with_system(paddle_control)
             );
     }
 }
 
-#[derive(Component)]
-struct Score(isize);
+#[derive(Component, Clone, Copy)]
+pub struct Score(pub isize);
 
-#[derive(Component, PartialEq, Debug)]
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
 pub enum Player {
     Left,
     Right,
 }
 
-impl Player {
-    fn move_up_key(&self) -> KeyCode {
-        match self {
-            Player::Left => KeyCode::W,
-            Player::Right => KeyCode::Up,
-        }
-    }
-
-    fn move_down_key(&self) -> KeyCode {
-        match self {
-            Player::Left => KeyCode::S,
-            Player::Right => KeyCode::Down,
-        }
-    }
-
-    fn push_right_key(&self) -> KeyCode {
-        match self {
-            Player::Left => KeyCode::D,
-            Player::Right => KeyCode::Right,
-        }
-    }
-
-    fn push_left_key(&self) -> KeyCode {
-        match self {
-            Player::Left => KeyCode::A,
-            Player::Right => KeyCode::Left,
-        }
-    }
-}
-
 #[derive(Bundle)]
 pub struct PlayerBundle {
     score: Score,
     player: Player,
+    control: Control,
+    controller: Controller,
+    intent: PaddleIntent,
     bounding_box: BoundingBox,
     velocity: Velocity,
+    interpolated: Interpolated,
 
     #[bundle]
     sprite: SpriteBundle,
 }
 
-fn setup_paddles(mut commands: Commands, windows: Res<Windows>) {
+pub fn setup_paddles(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    controls: Res<ControlSetup>,
+    rng: Res<GameRng>,
+) {
     let window = windows.get_primary().unwrap();
     let paddle_width = window.width() / SIZE_FACTOR;
     let paddle_height = paddle_width * 6.0;
@@ -84,11 +61,15 @@ fn setup_paddles(mut commands: Commands, windows: Res<Windows>) {
     commands.spawn_bundle(PlayerBundle {
         score: Score(0),
         player: Player::Left,
+        control: controls.left,
+        controller: Controller::for_control(controls.left, Player::Left, &rng),
+        intent: PaddleIntent::default(),
         bounding_box: BoundingBox {
             width: size.x,
             height: size.y,
         },
         velocity: Velocity { x: 0.0, y: 0.0 },
+        interpolated: Interpolated::default(),
 
         sprite: SpriteBundle {
             sprite: Sprite {
@@ -107,11 +88,15 @@ fn setup_paddles(mut commands: Commands, windows: Res<Windows>) {
     commands.spawn_bundle(PlayerBundle {
         score: Score(0),
         player: Player::Right,
+        control: controls.right,
+        controller: Controller::for_control(controls.right, Player::Right, &rng),
+        intent: PaddleIntent::default(),
         bounding_box: BoundingBox {
             width: size.x,
             height: size.y,
         },
         velocity: Velocity { x: 0.0, y: 0.0 },
+        interpolated: Interpolated::default(),
 
         sprite: SpriteBundle {
             sprite: Sprite {
@@ -128,58 +113,53 @@ fn setup_paddles(mut commands: Commands, windows: Res<Windows>) {
     });
 }
 
+/// Moves each paddle by its `PaddleIntent`, whatever set it.
 pub fn paddle_control(
-    mut paddle_q: Query<(&Player, &mut Transform, &mut Velocity)>,
-    keys: Res<Input<KeyCode>>,
-    time: Res<Time>,
+    mut paddle_q: Query<(&PaddleIntent, &mut Transform, &mut Velocity)>,
+    rules: Res<GameRules>,
 ) {
-    let player_speed = 300.0;
-    let push_speed = 133.0;
-    for (player, mut transform, mut velocity) in paddle_q.iter_mut() {
-        if keys.pressed(player.move_up_key()) {
-            transform.translation.y += player_speed * time.delta_seconds();
-            velocity.y = player_speed;
-        } else if keys.pressed(player.move_down_key()) {
-            transform.translation.y -= player_speed * time.delta_seconds();
-            velocity.y = -player_speed;
-        } else {
-            velocity.y = 0.0;
-        }
-
-        if keys.pressed(player.push_right_key()) {
-            velocity.x = push_speed;
-        } else if keys.pressed(player.push_left_key()) {
-            velocity.x = -push_speed;
-        } else {
-            velocity.x = 0.0;
-        }
+    for (intent, mut transform, mut velocity) in paddle_q.iter_mut() {
+        velocity.y = intent.vertical * rules.paddle_speed;
+        velocity.x = intent.push * rules.push_speed;
+        transform.translation.y += velocity.y * TICK_SECONDS;
     }
 }
 
-fn player_scored(
+pub fn player_scored(
     mut score_q: Query<(&mut Score, &Player)>,
     mut score_event: EventReader<ScoredEvent>,
     mut scoreboard_q: Query<&mut Text, With<Scoreboard>>,
-    mut state: ResMut<State<GameState>>,
+    mut sim: ResMut<SimState>,
+    rules: Res<MatchRules>,
+    mut winner: ResMut<Winner>,
 ) {
-    for scored in score_event.iter() {
-        let mut scoreboard = scoreboard_q.single_mut();
-        for (mut score, player) in score_q.iter_mut() {
-            if *player == scored.player {
-                if *state.current() != GameState::Scored {
-                    score.0 += 1;
-                    state.set(GameState::Scored).unwrap();
-                }
-            }
-
-            match player {
-                Player::Left => {
-                    scoreboard.sections[0].value = score.0.to_string();
-                }
-                Player::Right => {
-                    scoreboard.sections[2].value = score.0.to_string();
-                }
-            }
+    let scored = match score_event.iter().last() {
+        Some(scored) => scored,
+        None => return,
+    };
+
+    let mut scores = [0, 0];
+    for (mut score, player) in score_q.iter_mut() {
+        if *player == scored.player {
+            score.0 += 1;
+        }
+
+        match player {
+            Player::Left => scores[0] = score.0,
+            Player::Right => scores[1] = score.0,
         }
     }
+    update_scoreboard(&mut scoreboard_q.single_mut(), scores[0], scores[1]);
+
+    winner.0 = rules.winner(scores[0], scores[1]);
+    sim.0 = if winner.0.is_some() {
+        GameState::GameOver
+    } else {
+        GameState::Scored
+    };
+}
+
+pub fn update_scoreboard(scoreboard: &mut Text, left: isize, right: isize) {
+    scoreboard.sections[0].value = left.to_string();
+    scoreboard.sections[2].value = right.to_string();
 }
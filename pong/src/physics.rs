@@ -0,0 +1,388 @@
+use std::collections::HashSet;
+
+use bevy::{ecs::schedule::ShouldRun, transform::TransformSystem};
+
+use crate::prelude::*;
+
+/// Length of one physics tick. Everything in the `PhysicsStage` steps by
+/// exactly this much, however long the frame took.
+pub const TICK_SECONDS: f32 = 1.0 / 120.0;
+/// Most ticks one frame's time can add up to. After a long stall, such as
+/// dragging the window, the game carries on from where it was instead of
+/// racing through everything it missed. `fast_forward` isn't limited.
+const MAX_TICKS_PER_FRAME: f64 = 30.0;
+
+/// Runs after `CoreStage::Update` as many times as whole ticks have built up
+/// since the last frame, and not at all on frames that are too short.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub struct PhysicsStage;
+
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PhysicsClock>()
+            .insert_resource(SimState(GameState::Serving))
+            .add_system_to_stage(CoreStage::PreUpdate, follow_sim_state)
+            .add_stage_after(
+                CoreStage::Update,
+                PhysicsStage,
+                SystemStage::parallel().with_run_criteria(run_tick),
+            )
+            .add_system_to_stage(PhysicsStage, remember_positions)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                interpolate.after(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// What's holding the game still. Each is only added and removed by the
+/// plugin it's named after, so none can resume a pause another one wants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PauseReason {
+    /// The title or pause menu is up.
+    Menu,
+    /// A paddle's gamepad is unplugged.
+    Gamepad,
+    /// `Space` during playback.
+    Replay,
+    /// Not connected, or too far ahead of the other side.
+    Netplay,
+}
+
+/// Turns frame time into whole physics ticks, like `FixedTimestep`, but can
+/// be sped up, slowed down, paused and fast-forwarded for replays.
+pub struct PhysicsClock {
+    pub speed: f32,
+    paused: HashSet<PauseReason>,
+    accumulator: f64,
+    looping: bool,
+}
+
+impl Default for PhysicsClock {
+    fn default() -> Self {
+        PhysicsClock {
+            speed: 1.0,
+            paused: HashSet::new(),
+            accumulator: 0.0,
+            looping: false,
+        }
+    }
+}
+
+impl PhysicsClock {
+    pub fn pause(&mut self, reason: PauseReason) {
+        self.paused.insert(reason);
+    }
+
+    /// Lifts `reason`'s pause, leaving any others in place.
+    pub fn resume(&mut self, reason: PauseReason) {
+        self.paused.remove(&reason);
+    }
+
+    pub fn set_paused(&mut self, reason: PauseReason, paused: bool) {
+        if paused {
+            self.pause(reason);
+        } else {
+            self.resume(reason);
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        !self.paused.is_empty()
+    }
+
+    pub fn is_paused_by(&self, reason: PauseReason) -> bool {
+        self.paused.contains(&reason)
+    }
+
+    /// Runs `ticks` extra ticks as soon as possible, even while paused.
+    pub fn fast_forward(&mut self, ticks: usize) {
+        self.accumulator += ticks as f64 * TICK_SECONDS as f64;
+    }
+
+    /// How far it is from the last tick to the next one, from 0.0 to 1.0.
+    pub fn overstep(&self) -> f32 {
+        (self.accumulator / TICK_SECONDS as f64) as f32
+    }
+}
+
+/// Called again after every tick until the time built up runs out.
+fn run_tick(mut clock: ResMut<PhysicsClock>, time: Res<Time>) -> ShouldRun {
+    if !clock.looping && !clock.is_paused() {
+        let elapsed = time.delta_seconds_f64() * clock.speed as f64;
+        clock.accumulator += elapsed.min(MAX_TICKS_PER_FRAME * TICK_SECONDS as f64);
+    }
+
+    if clock.accumulator >= TICK_SECONDS as f64 {
+        clock.accumulator -= TICK_SECONDS as f64;
+        clock.looping = true;
+        ShouldRun::YesAndCheckAgain
+    } else {
+        clock.looping = false;
+        ShouldRun::No
+    }
+}
+
+/// The `GameState` as the simulation sees it. It only changes inside ticks,
+/// so the same inputs always change it on the same tick at any frame rate, and
+/// `State<GameState>` follows it at the start of the next frame for
+/// everything that's only drawn.
+pub struct SimState(pub GameState);
+
+/// Left alone while a menu is up, as the simulation is paused underneath.
+fn follow_sim_state(sim: Res<SimState>, mut state: ResMut<State<GameState>>) {
+    if matches!(state.current(), GameState::MainMenu | GameState::Paused) {
+        return;
+    }
+    if *state.current() != sim.0 {
+        let _ = state.overwrite_set(sim.0.clone());
+    }
+}
+
+// Run criteria for the `PhysicsStage`, where `SystemSet::on_update` can't be
+// used: the state's own criteria expect to share a stage with its driver.
+
+fn run_if(condition: bool) -> ShouldRun {
+    if condition {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+pub fn while_serving(sim: Res<SimState>) -> ShouldRun {
+    run_if(sim.0 == GameState::Serving)
+}
+
+pub fn while_playing(sim: Res<SimState>) -> ShouldRun {
+    run_if(sim.0 == GameState::Playing)
+}
+
+pub fn while_scored(sim: Res<SimState>) -> ShouldRun {
+    run_if(sim.0 == GameState::Scored)
+}
+
+pub fn while_resetting(sim: Res<SimState>) -> ShouldRun {
+    run_if(sim.0 == GameState::Resetting)
+}
+
+pub fn while_game_over(sim: Res<SimState>) -> ShouldRun {
+    run_if(sim.0 == GameState::GameOver)
+}
+
+/// While the paddles can move.
+pub fn while_on_court(sim: Res<SimState>) -> ShouldRun {
+    run_if(matches!(
+        sim.0,
+        GameState::Resetting | GameState::Serving | GameState::Playing
+    ))
+}
+
+/// Drawn part of the way between where it was before the last physics tick
+/// and where it is now, so it moves smoothly at any frame rate.
+#[derive(Component, Default)]
+pub struct Interpolated {
+    previous: Option<Vec3>,
+}
+
+pub fn remember_positions(mut interpolated_q: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in interpolated_q.iter_mut() {
+        interpolated.previous = Some(transform.translation);
+    }
+}
+
+/// Only touches the `GlobalTransform`, leaving `Transform` as the physics
+/// state.
+fn interpolate(
+    clock: Res<PhysicsClock>,
+    mut interpolated_q: Query<(&Transform, &Interpolated, &mut GlobalTransform)>,
+) {
+    let alpha = clock.overstep().min(1.0);
+
+    for (transform, interpolated, mut global) in interpolated_q.iter_mut() {
+        if let Some(previous) = interpolated.previous {
+            global.translation = previous.lerp(transform.translation, alpha);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    const TICKS: usize = 360;
+
+    /// Every tick's ball and paddles, in the order they were spawned.
+    #[derive(Default)]
+    struct TickLog(Vec<Vec<(Vec3, f32, f32, Option<isize>)>>);
+
+    fn log_tick(
+        mut log: ResMut<TickLog>,
+        moving_q: Query<(&Transform, &Velocity, Option<&Score>)>,
+    ) {
+        let tick = moving_q
+            .iter()
+            .map(|(transform, velocity, score)| {
+                (transform.translation, velocity.x, velocity.y, score.map(|score| score.0))
+            })
+            .collect();
+        log.0.push(tick);
+    }
+
+    /// What the left paddle presses on each tick, serving on the first.
+    fn left_intent(tick: usize) -> PaddleIntent {
+        PaddleIntent {
+            vertical: if tick < 50 { 1.0 } else { -0.4 },
+            push: if tick % 90 < 10 { 1.0 } else { 0.0 },
+            serve: tick == 0,
+        }
+    }
+
+    fn right_intent(tick: usize) -> PaddleIntent {
+        PaddleIntent {
+            vertical: (tick as f32 / 20.0).sin(),
+            ..default()
+        }
+    }
+
+    /// A serve and what follows, with both paddles scripted, logging every
+    /// tick until `TICKS` of them have run at frames of `frame` each.
+    fn play(frame: Duration) -> Vec<Vec<(Vec3, f32, f32, Option<isize>)>> {
+        let mut app = App::new();
+        app.insert_resource(Time::default())
+            .insert_resource(GameRules::default())
+            .insert_resource(GameRng::new(42))
+            .insert_resource(MatchRules::default())
+            .init_resource::<Winner>()
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<KeyBindings>()
+            .init_resource::<Input<GamepadButton>>()
+            .init_resource::<Axis<GamepadAxis>>()
+            .init_resource::<TickLog>()
+            .add_event::<CollisionEvent>()
+            .add_event::<ScoredEvent>()
+            .add_state(GameState::Serving)
+            .add_plugin(PhysicsPlugin)
+            .add_system_to_stage(PhysicsStage, read_controllers.after(remember_positions))
+            .add_system_set_to_stage(
+                PhysicsStage,
+                SystemSet::new()
+                    .with_run_criteria(while_on_court)
+                    .with_system(paddle_control.after(read_controllers))
+                    .with_system(court_collisions.after(ball_movement))
+            )
+            .add_system_set_to_stage(
+                PhysicsStage,
+                SystemSet::new()
+                    .with_run_criteria(while_serving)
+                    .with_system(serve_ball.after(paddle_control))
+            )
+            .add_system_set_to_stage(
+                PhysicsStage,
+                SystemSet::new()
+                    .with_run_criteria(while_playing)
+                    .with_system(ball_movement.after(paddle_control))
+                    .with_system(player_scored.after(court_collisions))
+            )
+            .add_system_to_stage(PhysicsStage, bounce.after(court_collisions))
+            .add_system_to_stage(
+                PhysicsStage,
+                log_tick.after(bounce).after(player_scored).after(serve_ball)
+            );
+
+        app.world
+            .spawn()
+            .insert(Court)
+            .insert(Transform::default())
+            .insert(BoundingBox { width: 400.0, height: 300.0 });
+        app.world
+            .spawn()
+            .insert(Ball {
+                hold_timer: Timer::from_seconds(1.0, false),
+                blink_timer: Timer::from_seconds(0.25, false),
+                reset_timer: Timer::from_seconds(2.0, false),
+            })
+            .insert(Velocity { x: -300.0, y: 0.0 })
+            .insert(Transform::default())
+            .insert(BoundingBox { width: 8.0, height: 8.0 });
+        let scripts: [(Player, f32, fn(usize) -> PaddleIntent); 2] =
+            [(Player::Left, -180.0, left_intent), (Player::Right, 180.0, right_intent)];
+        for (player, x, script) in scripts {
+            app.world
+                .spawn()
+                .insert(player)
+                .insert(Score(0))
+                .insert(Controller::new(ScriptedController {
+                    intents: (0..TICKS).map(script).collect(),
+                }))
+                .insert(PaddleIntent::default())
+                .insert(Velocity { x: 0.0, y: 0.0 })
+                .insert(Transform::from_xyz(x, 0.0, 0.0))
+                .insert(BoundingBox { width: 10.0, height: 60.0 });
+        }
+        app.world.spawn().insert(Scoreboard).insert(Text {
+            sections: vec![TextSection::default(); 4],
+            ..default()
+        });
+
+        let start = Instant::now();
+        app.world.resource_mut::<Time>().update_with_instant(start);
+        let mut frames = 0;
+        while app.world.resource::<TickLog>().0.len() < TICKS {
+            frames += 1;
+            app.world.resource_mut::<Time>().update_with_instant(start + frame * frames);
+            app.update();
+        }
+
+        let mut log = app.world.resource_mut::<TickLog>();
+        log.0.truncate(TICKS);
+        std::mem::take(&mut log.0)
+    }
+
+    #[test]
+    fn frame_rate_doesnt_change_the_simulation() {
+        let slow = play(Duration::from_secs_f64(1.0 / 30.0));
+        let fast = play(Duration::from_secs_f64(1.0 / 144.0));
+
+        for (tick, (slow, fast)) in slow.iter().zip(&fast).enumerate() {
+            assert_eq!(slow, fast, "tick {}", tick);
+        }
+    }
+
+    #[derive(Default)]
+    struct Ticks(usize);
+
+    fn count_tick(mut ticks: ResMut<Ticks>) {
+        ticks.0 += 1;
+    }
+
+    #[test]
+    fn long_frames_only_run_so_many_ticks() {
+        let mut app = App::new();
+        app.insert_resource(Time::default())
+            .init_resource::<Ticks>()
+            .add_state(GameState::Serving)
+            .add_plugin(PhysicsPlugin)
+            .add_system_to_stage(PhysicsStage, count_tick);
+
+        let start = Instant::now();
+        let mut time = app.world.resource_mut::<Time>();
+        time.update_with_instant(start);
+        time.update_with_instant(start + Duration::from_secs(5));
+        app.update();
+
+        assert_eq!(app.world.resource::<Ticks>().0, MAX_TICKS_PER_FRAME as usize);
+
+        // Fast-forwarding still runs every tick asked for.
+        app.world.resource_mut::<Ticks>().0 = 0;
+        app.world.resource_mut::<Time>().update_with_instant(start + Duration::from_secs(5));
+        app.world.resource_mut::<PhysicsClock>().fast_forward(100);
+        app.update();
+
+        assert_eq!(app.world.resource::<Ticks>().0, 100);
+    }
+}
@@ -0,0 +1,148 @@
+use std::{env, process};
+
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::prelude::*;
+
+pub struct RandomPlugin;
+
+impl Plugin for RandomPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(GameRng::from_args());
+
+        if cfg!(debug_assertions) {
+            app.add_startup_system(setup_seed_label);
+        }
+    }
+}
+
+/// The only source of randomness in a match, so the same seed and the same
+/// inputs always play out the same way. ChaCha8, unlike `StdRng`, is
+/// promised to give the same numbers on every platform and `rand` version,
+/// which replays and netplay rely on.
+#[derive(Clone)]
+pub struct GameRng {
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        GameRng {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// Seeds from `--seed <n>`, or from the OS when it's missing. Exits if
+    /// the seed isn't a number, rather than quietly playing some other match.
+    pub fn from_args() -> Self {
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "--seed" {
+                let value = args.next().unwrap_or_default();
+                match value.parse() {
+                    Ok(seed) => return GameRng::new(seed),
+                    Err(_) => {
+                        eprintln!(
+                            "--seed takes a whole number from 0 to {}, not {:?}",
+                            u64::MAX,
+                            value
+                        );
+                        process::exit(1);
+                    }
+                }
+            }
+        }
+
+        GameRng::new(rand::random())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+fn setup_seed_label(mut commands: Commands, asset_server: Res<AssetServer>, rng: Res<GameRng>) {
+    commands.spawn_bundle(TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: Rect {
+                right: Val::Px(10.0),
+                bottom: Val::Px(5.0),
+                ..default()
+            },
+            ..default()
+        },
+        text: Text::with_section(
+            format!("SEED {}", rng.seed()),
+            TextStyle {
+                font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                font_size: 20.0,
+                color: Color::rgb(0.6, 0.6, 0.6),
+            },
+            TextAlignment::default(),
+        ),
+        ..default()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serves(seed: u64) -> Vec<(f32, f32)> {
+        let rules = GameRules::default();
+        let mut rng = GameRng::new(seed);
+        (0..20)
+            .map(|_| {
+                let velocity = Velocity::random(&rules, &mut rng);
+                (velocity.x, velocity.y)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_same_seed_serves_the_same_way() {
+        assert_eq!(serves(1234), serves(1234));
+        assert_ne!(serves(1234), serves(1235));
+    }
+
+    #[test]
+    fn serves_stay_within_the_rules() {
+        let rules = GameRules::default();
+        for (x, y) in serves(99) {
+            assert!(rules.serve_speed_x.contains(&x.abs()));
+            assert!(rules.serve_speed_y.contains(&y.abs()));
+        }
+    }
+
+    #[test]
+    fn clones_carry_on_from_the_same_place() {
+        let mut rng = GameRng::new(5);
+        rng.next_u64();
+        let mut copy = rng.clone();
+
+        assert_eq!(copy.next_u64(), rng.next_u64());
+        assert_eq!(copy.seed(), 5);
+    }
+}
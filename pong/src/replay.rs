@@ -0,0 +1,662 @@
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::ecs::system::SystemParam;
+
+use crate::prelude::*;
+
+const REPLAY_DIR: &str = "replays";
+/// How far one press of Left or Right scrubs, in ticks.
+const SCRUB_TICKS: usize = 5 * 120;
+const SPEEDS: [f32; 5] = [0.25, 0.5, 1.0, 2.0, 4.0];
+
+/// Records every match into `replays/`, or with `--replay <file>` plays one
+/// back:
+///
+/// - `Space` pauses and resumes
+/// - `Up` and `Down` change the speed
+/// - `Left` and `Right` scrub back and forward
+/// - typing a number and pressing `Enter` jumps to that point
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let path = match replay_path_from_args() {
+            Some(path) => path,
+            None => {
+                app.insert_resource(Recorder::default())
+                    .add_system_set(
+                        SystemSet::on_enter(GameState::GameOver)
+                            .with_system(save_replay)
+                    )
+                    .add_system_set(
+                        SystemSet::on_exit(GameState::GameOver)
+                            .with_system(clear_recording)
+                    )
+                    // Whatever was left of a match abandoned from the menu.
+                    .add_system_set(
+                        SystemSet::on_enter(GameState::MainMenu)
+                            .with_system(clear_recording)
+                    )
+                    .add_system_set_to_stage(
+                        PhysicsStage,
+                        SystemSet::new()
+                            .with_run_criteria(while_serving)
+                            .with_system(start_point.after(serve_ball))
+                    )
+                    .add_system_set_to_stage(
+                        PhysicsStage,
+                        SystemSet::new()
+                            .with_run_criteria(while_playing)
+                            .with_system(record_intents.after(paddle_control))
+                    );
+                return;
+            }
+        };
+
+        let replay = match Replay::load(&path) {
+            Ok(replay) => replay,
+            Err(error) => {
+                eprintln!("Couldn't load the replay {}: {}", path.display(), error);
+                process::exit(1);
+            }
+        };
+
+        app.insert_resource(GameRng::new(replay.seed))
+            .insert_resource(replay.rules.clone())
+            .insert_resource(replay.match_rules.clone())
+            .insert_resource(Playback::new(replay))
+            .add_startup_system(take_over_paddles.after(setup_paddles))
+            .add_system(playback_controls)
+            .add_system(playback_serve.after(playback_controls))
+            .add_system(show_playback_status)
+            .add_system_set_to_stage(
+                PhysicsStage,
+                SystemSet::new()
+                    .with_run_criteria(while_playing)
+                    .with_system(count_ticks)
+            );
+    }
+}
+
+fn replay_path_from_args() -> Option<PathBuf> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--replay" {
+            return args.next().map(PathBuf::from);
+        }
+    }
+    None
+}
+
+fn side(player: &Player) -> usize {
+    match player {
+        Player::Left => 0,
+        Player::Right => 1,
+    }
+}
+
+/// The court as a point was served.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Snapshot {
+    pub scores: [isize; 2],
+    pub paddles: [f32; 2],
+    pub ball: Vec2,
+    pub ball_velocity: Vec2,
+}
+
+/// One rally, from the serve to whoever scored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Point {
+    pub snapshot: Snapshot,
+    /// Both paddles' intents, left then right, for every tick in play.
+    pub ticks: Vec<[PaddleIntent; 2]>,
+}
+
+/// Everything needed to play a match out again exactly.
+///
+/// Saved as text: a `pong replay` header, then the seed, the match rules and
+/// the physics rules, then each point's snapshot followed by one line of
+/// intents per tick. Serving isn't saved, as each point starts from its
+/// snapshot.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    /// What the `GameRng` was seeded with, and is again for playback.
+    pub seed: u64,
+    pub match_rules: MatchRules,
+    pub rules: GameRules,
+    pub points: Vec<Point>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Replay, String> {
+        fs::read_to_string(path)
+            .map_err(|error| error.to_string())?
+            .parse()
+    }
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rules = &self.rules;
+        writeln!(f, "pong replay")?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(
+            f,
+            "match {} {}",
+            self.match_rules.winning_score, self.match_rules.win_by_two
+        )?;
+        writeln!(
+            f,
+            "rules {} {} {} {} {} {} {} {} {} {}",
+            rules.serve_speed_x.start,
+            rules.serve_speed_x.end,
+            rules.serve_speed_y.start,
+            rules.serve_speed_y.end,
+            rules.paddle_speed,
+            rules.push_speed,
+            rules.max_bounce_angle,
+            rules.paddle_spin,
+            rules.speed_up,
+            rules.max_ball_speed
+        )?;
+
+        for point in &self.points {
+            let snapshot = &point.snapshot;
+            writeln!(
+                f,
+                "point {} {} {} {} {} {} {} {}",
+                snapshot.scores[0],
+                snapshot.scores[1],
+                snapshot.paddles[0],
+                snapshot.paddles[1],
+                snapshot.ball.x,
+                snapshot.ball.y,
+                snapshot.ball_velocity.x,
+                snapshot.ball_velocity.y
+            )?;
+            for [left, right] in &point.ticks {
+                writeln!(
+                    f,
+                    "{} {} {} {}",
+                    left.vertical, left.push, right.vertical, right.push
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses the whitespace-separated values after a line's keyword.
+fn values<T: FromStr>(line: &str, keyword: &str, count: usize) -> Result<Vec<T>, String> {
+    let rest = line
+        .strip_prefix(keyword)
+        .ok_or_else(|| format!("expected `{}`, found `{}`", keyword, line))?;
+    let values = rest
+        .split_whitespace()
+        .map(|value| value.parse().map_err(|_| format!("bad value `{}`", value)))
+        .collect::<Result<Vec<T>, String>>()?;
+
+    if values.len() != count {
+        return Err(format!("expected {} values in `{}`", count, line));
+    }
+    Ok(values)
+}
+
+impl FromStr for Replay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().map(str::trim).filter(|line| !line.is_empty());
+        let mut next = |what: &str| lines.next().ok_or_else(|| format!("missing {}", what));
+
+        if next("header")? != "pong replay" {
+            return Err("not a pong replay".to_string());
+        }
+        let seed = values::<u64>(next("seed")?, "seed", 1)?[0];
+
+        let match_line = next("match rules")?;
+        let match_values = values::<String>(match_line, "match", 2)?;
+        let match_rules = MatchRules {
+            winning_score: match_values[0]
+                .parse()
+                .map_err(|_| format!("bad winning score in `{}`", match_line))?,
+            win_by_two: match_values[1]
+                .parse()
+                .map_err(|_| format!("bad win by two in `{}`", match_line))?,
+        };
+
+        let r = values::<f32>(next("rules")?, "rules", 10)?;
+        let rules = GameRules {
+            serve_speed_x: r[0]..r[1],
+            serve_speed_y: r[2]..r[3],
+            paddle_speed: r[4],
+            push_speed: r[5],
+            max_bounce_angle: r[6],
+            paddle_spin: r[7],
+            speed_up: r[8],
+            max_ball_speed: r[9],
+        };
+
+        let mut points: Vec<Point> = Vec::new();
+        for line in lines {
+            if line.starts_with("point") {
+                let p = values::<f32>(line, "point", 8)?;
+                points.push(Point {
+                    snapshot: Snapshot {
+                        scores: [p[0] as isize, p[1] as isize],
+                        paddles: [p[2], p[3]],
+                        ball: Vec2::new(p[4], p[5]),
+                        ball_velocity: Vec2::new(p[6], p[7]),
+                    },
+                    ticks: Vec::new(),
+                });
+                continue;
+            }
+
+            let point = points
+                .last_mut()
+                .ok_or_else(|| "intents before the first point".to_string())?;
+            let t = values::<f32>(line, "", 4)?;
+            point.ticks.push([
+                PaddleIntent {
+                    vertical: t[0],
+                    push: t[1],
+                    serve: false,
+                },
+                PaddleIntent {
+                    vertical: t[2],
+                    push: t[3],
+                    serve: false,
+                },
+            ]);
+        }
+
+        Ok(Replay {
+            seed,
+            match_rules,
+            rules,
+            points,
+        })
+    }
+}
+
+/// The replay being recorded of the match in progress.
+#[derive(Default)]
+struct Recorder {
+    points: Vec<Point>,
+}
+
+/// Takes the snapshot on the tick the ball is served. Online matches aren't
+/// recorded, since rolling back would record the same ticks again.
+fn start_point(
+    mut recorder: ResMut<Recorder>,
+    paddle_q: Query<(&Player, &Score, &Transform)>,
+    ball_q: Query<(&Transform, &Velocity), With<Ball>>,
+    sim: Res<SimState>,
+    netplay: Option<Res<Netplay>>,
+) {
+    if sim.0 != GameState::Playing || netplay.is_some() {
+        return;
+    }
+
+    let (ball_t, ball_v) = ball_q.single();
+    let mut snapshot = Snapshot {
+        scores: [0, 0],
+        paddles: [0.0, 0.0],
+        ball: Vec2::new(ball_t.translation.x, ball_t.translation.y),
+        ball_velocity: Vec2::new(ball_v.x, ball_v.y),
+    };
+    for (player, score, transform) in paddle_q.iter() {
+        snapshot.scores[side(player)] = score.0;
+        snapshot.paddles[side(player)] = transform.translation.y;
+    }
+
+    recorder.points.push(Point {
+        snapshot,
+        ticks: Vec::new(),
+    });
+}
+
+fn record_intents(mut recorder: ResMut<Recorder>, paddle_q: Query<(&Player, &PaddleIntent)>) {
+    let mut intents = [PaddleIntent::default(); 2];
+    for (player, intent) in paddle_q.iter() {
+        intents[side(player)] = *intent;
+    }
+    if let Some(point) = recorder.points.last_mut() {
+        point.ticks.push(intents);
+    }
+}
+
+fn save_replay(
+    recorder: Res<Recorder>,
+    rng: Res<GameRng>,
+    rules: Res<GameRules>,
+    match_rules: Res<MatchRules>,
+) {
+    if recorder.points.is_empty() {
+        return;
+    }
+
+    let replay = Replay {
+        seed: rng.seed(),
+        match_rules: match_rules.clone(),
+        rules: rules.clone(),
+        points: recorder.points.clone(),
+    };
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let path = Path::new(REPLAY_DIR).join(format!("replay-{}.txt", timestamp));
+
+    let saved = fs::create_dir_all(REPLAY_DIR).and_then(|_| fs::write(&path, replay.to_string()));
+    match saved {
+        Ok(()) => info!("Saved the replay to {}", path.display()),
+        Err(error) => warn!("Couldn't save the replay to {}: {}", path.display(), error),
+    }
+}
+
+fn clear_recording(mut recorder: ResMut<Recorder>) {
+    recorder.points.clear();
+}
+
+/// A replay being played back.
+pub struct Playback {
+    replay: Replay,
+    /// The point in play and how many of its ticks have run.
+    point: usize,
+    tick: usize,
+    /// Where to pick up from at the next serve instead of the next point.
+    seek: Option<(usize, usize)>,
+    /// The point number being typed in to jump to.
+    typed: Option<usize>,
+}
+
+impl Playback {
+    fn new(replay: Replay) -> Self {
+        Playback {
+            replay,
+            point: 0,
+            tick: 0,
+            seek: Some((0, 0)),
+            typed: None,
+        }
+    }
+}
+
+/// Hands both paddles to scripts, which each serve refills from the replay.
+fn take_over_paddles(mut commands: Commands, paddle_q: Query<Entity, With<Player>>) {
+    for entity in paddle_q.iter() {
+        commands
+            .entity(entity)
+            .remove::<Control>()
+            .insert(Controller::new(ScriptedController::default()));
+    }
+}
+
+/// What the point before left behind, cleared away by each serve.
+#[derive(SystemParam)]
+struct LastPoint<'w, 's> {
+    scoreboard_q: Query<'w, 's, &'static mut Text, With<Scoreboard>>,
+    scored_events: ResMut<'w, Events<ScoredEvent>>,
+    winner: ResMut<'w, Winner>,
+}
+
+/// Once the simulation is waiting to serve, sets the court up as the next
+/// point, or the one being seeked to, was served, then fast-forwards to the
+/// right tick.
+fn playback_serve(
+    mut playback: ResMut<Playback>,
+    mut clock: ResMut<PhysicsClock>,
+    mut paddle_q: Query<(&Player, &mut Score, &mut Transform, &mut Controller), Without<Ball>>,
+    mut ball_q: Query<(&mut Ball, &mut Transform, &mut Velocity, &mut Visibility), Without<Player>>,
+    mut last_point: LastPoint,
+    mut sim: ResMut<SimState>,
+) {
+    if sim.0 != GameState::Serving {
+        return;
+    }
+
+    let (point, tick) = playback.seek.unwrap_or((playback.point + 1, 0));
+    let snapshot = match playback.replay.points.get(point) {
+        Some(point) => point.snapshot,
+        None => return,
+    };
+    playback.seek = None;
+    playback.point = point;
+    playback.tick = 0;
+
+    for (player, mut score, mut transform, mut controller) in paddle_q.iter_mut() {
+        let side = side(player);
+        score.0 = snapshot.scores[side];
+        transform.translation.y = snapshot.paddles[side];
+        *controller = Controller::new(ScriptedController {
+            intents: playback.replay.points[point]
+                .ticks
+                .iter()
+                .map(|intents| intents[side])
+                .collect(),
+        });
+    }
+
+    let (mut ball, mut transform, mut velocity, mut visibility) = ball_q.single_mut();
+    transform.translation.x = snapshot.ball.x;
+    transform.translation.y = snapshot.ball.y;
+    velocity.x = snapshot.ball_velocity.x;
+    velocity.y = snapshot.ball_velocity.y;
+    ball.hold_timer.reset();
+    ball.reset_timer.reset();
+    ball.blink_timer.pause();
+    visibility.is_visible = true;
+
+    update_scoreboard(
+        &mut last_point.scoreboard_q.single_mut(),
+        snapshot.scores[0],
+        snapshot.scores[1],
+    );
+    last_point.scored_events.clear();
+    last_point.winner.0 = None;
+    clock.fast_forward(tick);
+    sim.0 = GameState::Playing;
+}
+
+fn count_ticks(mut playback: ResMut<Playback>) {
+    playback.tick += 1;
+}
+
+/// Left to the menu while it's open, which uses some of the same keys.
+fn playback_controls(
+    keys: Res<Input<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut playback: ResMut<Playback>,
+    mut clock: ResMut<PhysicsClock>,
+    mut sim: ResMut<SimState>,
+) {
+    if matches!(state.current(), GameState::MainMenu | GameState::Paused) {
+        return;
+    }
+    if keys.just_pressed(KeyCode::Space) {
+        let paused = clock.is_paused_by(PauseReason::Replay);
+        clock.set_paused(PauseReason::Replay, !paused);
+    }
+
+    let speed = SPEEDS.iter().position(|speed| *speed == clock.speed).unwrap_or(2);
+    if keys.just_pressed(KeyCode::Up) {
+        clock.speed = SPEEDS[usize::min(speed + 1, SPEEDS.len() - 1)];
+    } else if keys.just_pressed(KeyCode::Down) {
+        clock.speed = SPEEDS[speed.saturating_sub(1)];
+    }
+
+    let digits = [
+        KeyCode::Key0,
+        KeyCode::Key1,
+        KeyCode::Key2,
+        KeyCode::Key3,
+        KeyCode::Key4,
+        KeyCode::Key5,
+        KeyCode::Key6,
+        KeyCode::Key7,
+        KeyCode::Key8,
+        KeyCode::Key9,
+    ];
+    for (digit, key) in digits.iter().enumerate() {
+        if keys.just_pressed(*key) {
+            playback.typed = type_digit(playback.typed, digit, playback.replay.points.len());
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        playback.typed = None;
+    }
+
+    let (point, tick) = (playback.point, playback.tick);
+    let ticks = playback.replay.points.get(point).map_or(0, |point| point.ticks.len());
+    let seek = if keys.just_pressed(KeyCode::Return) {
+        // Points are numbered from 1 on screen.
+        playback
+            .typed
+            .take()
+            .and_then(|number| number.checked_sub(1))
+            .filter(|number| *number < playback.replay.points.len())
+            .map(|number| (number, 0))
+    } else if keys.just_pressed(KeyCode::Left) {
+        Some((point, tick.saturating_sub(SCRUB_TICKS)))
+    } else if keys.just_pressed(KeyCode::Right) {
+        if tick + SCRUB_TICKS < ticks {
+            Some((point, tick + SCRUB_TICKS))
+        } else if point + 1 < playback.replay.points.len() {
+            Some((point + 1, 0))
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+
+    if let Some(seek) = seek {
+        playback.seek = Some(seek);
+        // `playback_serve` picks it up from there.
+        sim.0 = GameState::Serving;
+    }
+}
+
+/// `typed` with `digit` added on the end, unless that would be past the last
+/// of `points`, which also keeps it from overflowing.
+fn type_digit(typed: Option<usize>, digit: usize, points: usize) -> Option<usize> {
+    let longer = typed
+        .unwrap_or(0)
+        .checked_mul(10)
+        .and_then(|number| number.checked_add(digit));
+    match longer {
+        Some(number) if number <= points => Some(number),
+        _ => typed,
+    }
+}
+
+fn show_playback_status(
+    playback: Res<Playback>,
+    clock: Res<PhysicsClock>,
+    mut scoreboard_q: Query<&mut Text, With<Scoreboard>>,
+) {
+    let mut status = format!(
+        "\nREPLAY  POINT {}/{}  {}x",
+        playback.point + 1,
+        playback.replay.points.len(),
+        clock.speed
+    );
+    if clock.is_paused_by(PauseReason::Replay) {
+        status.push_str("  PAUSED");
+    }
+    if let Some(typed) = playback.typed {
+        status.push_str(&format!("  JUMP TO {}_", typed));
+    }
+
+    let mut scoreboard = scoreboard_q.single_mut();
+    if scoreboard.sections[3].value != status {
+        scoreboard.sections[3].value = status;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn intent(vertical: f32, push: f32) -> PaddleIntent {
+        PaddleIntent {
+            vertical,
+            push,
+            serve: false,
+        }
+    }
+
+    #[test]
+    fn replays_read_back_what_they_wrote() {
+        let replay = Replay {
+            seed: u64::MAX - 11,
+            match_rules: MatchRules {
+                winning_score: 5,
+                win_by_two: true,
+            },
+            rules: GameRules::default(),
+            points: vec![
+                Point {
+                    snapshot: Snapshot {
+                        scores: [0, 0],
+                        paddles: [-12.5, 0.1],
+                        ball: Vec2::new(0.0, -33.0),
+                        ball_velocity: Vec2::new(-251.37, 98.2),
+                    },
+                    ticks: vec![
+                        [intent(1.0, 0.0), intent(-0.3125, 1.0)],
+                        [intent(0.0, -1.0), intent(0.7, 0.0)],
+                    ],
+                },
+                Point {
+                    snapshot: Snapshot {
+                        scores: [0, 1],
+                        paddles: [40.0, -7.75],
+                        ball: Vec2::new(0.0, -33.0),
+                        ball_velocity: Vec2::new(310.0, -1.0 / 3.0),
+                    },
+                    ticks: vec![],
+                },
+            ],
+        };
+
+        let parsed: Replay = replay.to_string().parse().unwrap();
+
+        assert_eq!(parsed, replay);
+    }
+
+    #[test]
+    fn other_files_are_not_replays() {
+        assert!("pong save\nseed 1\nmatch 11 false".parse::<Replay>().is_err());
+        assert!("pong replay\nseed 1\nmatch 11 maybe".parse::<Replay>().is_err());
+        assert!("pong replay\nseed -1\nmatch 11 false".parse::<Replay>().is_err());
+    }
+
+    #[test]
+    fn typed_point_numbers_stop_at_the_last_point() {
+        let typed = [1, 2].iter().fold(None, |typed, digit| type_digit(typed, *digit, 30));
+        assert_eq!(typed, Some(12));
+
+        // A third digit would be past point 30.
+        assert_eq!(type_digit(Some(12), 5, 30), Some(12));
+        assert_eq!(type_digit(Some(3), 0, 30), Some(30));
+        assert_eq!(type_digit(Some(3), 1, 30), Some(3));
+    }
+
+    #[test]
+    fn typing_never_overflows() {
+        let mut typed = None;
+        for _ in 0..40 {
+            typed = type_digit(typed, 9, usize::MAX);
+        }
+
+        assert!(typed.unwrap() > usize::MAX / 10);
+    }
+}
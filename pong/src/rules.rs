@@ -0,0 +1,38 @@
+use std::ops::Range;
+
+/// Tunable numbers for how the ball and paddles move. Speeds are in pixels
+/// per second and angles in radians.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameRules {
+    /// Horizontal serve speed, picked at random in this range.
+    pub serve_speed_x: Range<f32>,
+    /// Vertical serve speed, picked at random in this range.
+    pub serve_speed_y: Range<f32>,
+    pub paddle_speed: f32,
+    /// How hard a paddle pushes the ball when moving sideways.
+    pub push_speed: f32,
+    /// The rebound angle off the very edge of a paddle; hits in between
+    /// scale linearly down to straight back at the centre.
+    pub max_bounce_angle: f32,
+    /// How much of the paddle's vertical speed carries over to the ball.
+    pub paddle_spin: f32,
+    /// The ball's speed is multiplied by this on every paddle hit...
+    pub speed_up: f32,
+    /// ...up to this.
+    pub max_ball_speed: f32,
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        GameRules {
+            serve_speed_x: 200.0..400.0,
+            serve_speed_y: 80.0..300.0,
+            paddle_speed: 300.0,
+            push_speed: 133.0,
+            max_bounce_angle: 60f32.to_radians(),
+            paddle_spin: 0.2,
+            speed_up: 1.05,
+            max_ball_speed: 1200.0,
+        }
+    }
+}
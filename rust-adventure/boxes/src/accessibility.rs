@@ -0,0 +1,147 @@
+use crate::board::Board;
+use crate::theme::{ActiveTheme, Theme};
+use crate::{colors, storage, Points};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const SETTINGS_FILE: &str = "accessibility.ron";
+/// Pips run from one to this many before switching shape.
+const CUE_PIPS: u32 = 6;
+
+pub struct AccessibilityPlugin;
+
+impl Plugin for AccessibilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(storage::load::<AccessibilitySettings>(SETTINGS_FILE))
+            .add_systems(Update, (change_settings, render_tile_cues));
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Palette {
+    /// Whatever colours the active theme uses.
+    #[default]
+    Theme,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+    HighContrast,
+}
+
+impl Palette {
+    fn next(self) -> Self {
+        match self {
+            Palette::Theme => Palette::Deuteranopia,
+            Palette::Deuteranopia => Palette::Protanopia,
+            Palette::Protanopia => Palette::Tritanopia,
+            Palette::Tritanopia => Palette::HighContrast,
+            Palette::HighContrast => Palette::Theme,
+        }
+    }
+
+    /// Overrides the theme's colours with this palette's.
+    pub fn apply(&self, theme: &mut Theme) {
+        let tiles = match self {
+            Palette::Theme => return,
+            Palette::Deuteranopia => colors::DEUTERANOPIA_TILES,
+            Palette::Protanopia => colors::PROTANOPIA_TILES,
+            Palette::Tritanopia => colors::TRITANOPIA_TILES,
+            Palette::HighContrast => colors::HIGH_CONTRAST_TILES,
+        };
+
+        theme.tiles = tiles.to_vec();
+        theme.text = Color::BLACK;
+        if *self == Palette::HighContrast {
+            theme.background = colors::HIGH_CONTRAST_BOARD;
+            theme.board = colors::HIGH_CONTRAST_BOARD;
+            theme.placeholder = colors::HIGH_CONTRAST_PLACEHOLDER;
+        } else {
+            theme.board = colors::BOARD;
+            theme.placeholder = colors::TILE_PLACEHOLDER;
+        }
+    }
+}
+
+#[derive(Resource, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessibilitySettings {
+    pub palette: Palette,
+    /// Mark every tile with a per-value pattern, so colour isn't the only cue.
+    pub tile_cues: bool,
+}
+
+#[derive(Component)]
+struct TileCue;
+
+/// `C` cycles the palette and `V` toggles the tile cues.
+fn change_settings(keys: Res<Input<KeyCode>>, mut settings: ResMut<AccessibilitySettings>) {
+    if keys.just_pressed(KeyCode::C) {
+        settings.palette = settings.palette.next();
+    } else if keys.just_pressed(KeyCode::V) {
+        settings.tile_cues = !settings.tile_cues;
+    } else {
+        return;
+    }
+
+    storage::save(SETTINGS_FILE, &*settings);
+}
+
+/// Gives each tile a row of pips along its bottom edge: one to six square
+/// pips for 2 to 64, then one to six diamonds for 128 to 4096, and so on.
+fn render_tile_cues(
+    mut commands: Commands,
+    settings: Res<AccessibilitySettings>,
+    active: Res<ActiveTheme>,
+    boards: Query<Ref<Board>>,
+    tiles: Query<(Entity, Ref<Points>, Option<&Children>)>,
+    cues: Query<(), With<TileCue>>,
+) {
+    let board = boards.single();
+    let redraw_all = settings.is_changed() || active.is_changed() || board.is_changed();
+
+    for (entity, points, children) in tiles.iter() {
+        if !redraw_all && !points.is_changed() {
+            continue;
+        }
+
+        if let Some(children) = children {
+            for child in children.iter() {
+                if cues.contains(*child) {
+                    commands.entity(*child).despawn_recursive();
+                }
+            }
+        }
+        if !settings.tile_cues {
+            continue;
+        }
+
+        let step = points.value.max(2).ilog2() - 1;
+        let pips = step % CUE_PIPS + 1;
+        let diamonds = (step / CUE_PIPS) % 2 == 1;
+        let pip_size = board.tile_size * 0.08;
+        let spacing = pip_size * 1.6;
+        let first_x = -spacing * (pips - 1) as f32 / 2.0;
+        let y = -board.tile_size / 2.0 + pip_size * 1.2;
+
+        commands.entity(entity).with_children(|builder| {
+            for pip in 0..pips {
+                let mut transform = Transform::from_xyz(first_x + spacing * pip as f32, y, 1.0);
+                if diamonds {
+                    transform.rotate_z(std::f32::consts::FRAC_PI_4);
+                }
+
+                builder
+                    .spawn(SpriteBundle {
+                        sprite: Sprite {
+                            color: active.theme.text,
+                            custom_size: Some(Vec2::splat(pip_size)),
+                            ..default()
+                        },
+                        transform,
+                        ..default()
+                    })
+                    .insert(TileCue);
+            }
+        });
+    }
+}
@@ -0,0 +1,540 @@
+use crate::events::{GameEnded, MoveMade};
+use crate::game::Game;
+use crate::stats::{self, GameStats, LifetimeStats};
+use crate::theme::ActiveTheme;
+use crate::{storage, AppState, CurrentGame};
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    ecs::system::SystemParam,
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    utils::BoxedFuture,
+    window::PrimaryWindow,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const ACHIEVEMENTS_FILE: &str = "achievements.ron";
+const TOAST_SECONDS: f32 = 3.0;
+
+/// The achievements, loaded with the other assets.
+const ACHIEVEMENTS_ASSET: &str = "achievements/default.achievements.ron";
+
+/// What has to happen for an achievement to unlock.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub enum Condition {
+    ReachTile(u32),
+    ReachTileWithoutUndo(u32),
+    ReachTileWithinMoves { tile: u32, moves: u32 },
+    MergesInOneMove(u32),
+    WinOnBoardSize(u8),
+    GamesPlayed(u32),
+}
+
+impl Condition {
+    /// Whether it's met by `game` as it stands, given the stats so far, the
+    /// most merges in one of the moves just made and whether the game was
+    /// just won.
+    pub fn is_met(
+        &self,
+        game: &Game,
+        stats: &GameStats,
+        lifetime: &LifetimeStats,
+        largest_merge: u32,
+        won: bool,
+    ) -> bool {
+        let max_tile = game.grid.max_tile();
+        match *self {
+            Condition::ReachTile(tile) => max_tile >= tile,
+            Condition::ReachTileWithoutUndo(tile) => max_tile >= tile && stats.undos == 0,
+            Condition::ReachTileWithinMoves { tile, moves } => {
+                max_tile >= tile && stats.moves <= moves
+            }
+            Condition::MergesInOneMove(merges) => largest_merge >= merges,
+            Condition::WinOnBoardSize(size) => won && game.grid.size() == size,
+            Condition::GamesPlayed(games) => lifetime.games_played >= games,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct Achievement {
+    /// What unlocks are saved under, so it mustn't change once released.
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub condition: Condition,
+}
+
+/// Every achievement there is, in the order the gallery lists them.
+#[derive(Deserialize, TypeUuid, TypePath, Clone, Debug)]
+#[uuid = "0d6c2f71-8a3e-4c1b-b5f4-6e2a9d8c7b10"]
+pub struct Achievements(pub Vec<Achievement>);
+
+#[derive(Default)]
+pub struct AchievementsLoader;
+
+impl AssetLoader for AchievementsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let achievements: Achievements = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(achievements));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["achievements.ron"]
+    }
+}
+
+#[derive(Resource)]
+struct AchievementsHandle(Handle<Achievements>);
+
+#[derive(SystemParam)]
+struct AchievementList<'w> {
+    handle: Res<'w, AchievementsHandle>,
+    assets: Res<'w, Assets<Achievements>>,
+}
+
+impl AchievementList<'_> {
+    /// Empty until the asset has loaded.
+    fn get(&self) -> &[Achievement] {
+        self.assets
+            .get(&self.handle.0)
+            .map_or(&[], |achievements| &achievements.0)
+    }
+}
+
+pub struct AchievementsPlugin;
+
+impl Plugin for AchievementsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Achievements>()
+            .init_asset_loader::<AchievementsLoader>()
+            .insert_resource(storage::load::<Unlocked>(ACHIEVEMENTS_FILE))
+            .init_resource::<Toasts>()
+            .add_event::<AchievementUnlocked>()
+            .add_systems(Startup, (load_achievements, spawn_toast))
+            .add_systems(
+                Update,
+                (
+                    evaluate_achievements.after(stats::track_game_stats),
+                    queue_toasts.after(evaluate_achievements),
+                    show_toasts.after(queue_toasts),
+                ),
+            )
+            .add_systems(OnEnter(AppState::Achievements), spawn_gallery)
+            .add_systems(OnExit(AppState::Achievements), despawn_gallery);
+    }
+}
+
+#[derive(Event)]
+pub struct AchievementUnlocked {
+    pub achievement: Achievement,
+}
+
+/// Unlock times, as seconds since the Unix epoch, keyed by achievement id.
+#[derive(Resource, Default, Serialize, Deserialize)]
+pub struct Unlocked(pub BTreeMap<String, u64>);
+
+#[derive(Resource, Default)]
+struct Toasts {
+    queue: VecDeque<Achievement>,
+    timer: Option<Timer>,
+}
+
+#[derive(Component)]
+struct Toast;
+
+#[derive(Component)]
+struct Gallery;
+
+fn load_achievements(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(AchievementsHandle(asset_server.load(ACHIEVEMENTS_ASSET)));
+}
+
+fn evaluate_achievements(
+    achievements: AchievementList,
+    game: Res<CurrentGame>,
+    stats: Res<GameStats>,
+    lifetime: Res<LifetimeStats>,
+    mut moves: EventReader<MoveMade>,
+    mut ended: EventReader<GameEnded>,
+    mut unlocked: ResMut<Unlocked>,
+    mut unlocked_events: EventWriter<AchievementUnlocked>,
+) {
+    let largest_merge = moves.iter().map(|made| made.merges).max();
+    let ended: Vec<bool> = ended.iter().map(|game_ended| game_ended.won).collect();
+    if largest_merge.is_none() && ended.is_empty() {
+        return;
+    }
+
+    let won = ended.contains(&true);
+    let mut changed = false;
+
+    for achievement in achievements.get() {
+        if unlocked.0.contains_key(&achievement.id) {
+            continue;
+        }
+
+        let largest_merge = largest_merge.unwrap_or(0);
+        if achievement
+            .condition
+            .is_met(&game, &stats, &lifetime, largest_merge, won)
+        {
+            let unlocked_at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since| since.as_secs())
+                .unwrap_or(0);
+            unlocked.0.insert(achievement.id.clone(), unlocked_at);
+            unlocked_events.send(AchievementUnlocked {
+                achievement: achievement.clone(),
+            });
+            changed = true;
+        }
+    }
+
+    if changed {
+        storage::save(ACHIEVEMENTS_FILE, &*unlocked);
+    }
+}
+
+fn spawn_toast(mut commands: Commands, active: Res<ActiveTheme>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(10.0),
+                right: Val::Px(10.0),
+                padding: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            background_color: active.theme.placeholder.into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        })
+        .insert(Toast)
+        .with_children(|builder| {
+            builder.spawn(TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 18.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn queue_toasts(mut unlocked: EventReader<AchievementUnlocked>, mut toasts: ResMut<Toasts>) {
+    for event in unlocked.iter() {
+        toasts.queue.push_back(event.achievement.clone());
+    }
+}
+
+fn show_toasts(
+    time: Res<Time>,
+    mut toasts: ResMut<Toasts>,
+    mut toast_q: Query<(&mut Visibility, &Children), With<Toast>>,
+    mut texts: Query<&mut Text>,
+) {
+    let (mut visibility, children) = toast_q.single_mut();
+
+    if let Some(timer) = toasts.timer.as_mut() {
+        if !timer.tick(time.delta()).finished() {
+            return;
+        }
+        toasts.timer = None;
+        *visibility = Visibility::Hidden;
+    }
+
+    let Some(achievement) = toasts.queue.pop_front() else {
+        return;
+    };
+
+    for child in children.iter() {
+        if let Ok(mut text) = texts.get_mut(*child) {
+            text.sections[0].value = format!(
+                "Achievement unlocked: {}\n{}",
+                achievement.name, achievement.description
+            );
+        }
+    }
+    *visibility = Visibility::Visible;
+    toasts.timer = Some(Timer::from_seconds(TOAST_SECONDS, TimerMode::Once));
+}
+
+fn spawn_gallery(
+    mut commands: Commands,
+    achievements: AchievementList,
+    unlocked: Res<Unlocked>,
+    active: Res<ActiveTheme>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+) {
+    let window = windows.single();
+    let (width, height) = (window.width(), window.height());
+    let achievements = achievements.get();
+    let row_height = height * 0.9 / achievements.len().max(1) as f32;
+    let font_size = (row_height * 0.6).max(8.0);
+
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                color: active.theme.board,
+                custom_size: Some(Vec2::new(width, height)),
+                ..default()
+            },
+            transform: Transform::from_xyz(0.0, 0.0, 10.0),
+            ..default()
+        })
+        .insert(Gallery)
+        .with_children(|builder| {
+            for (i, achievement) in achievements.iter().enumerate() {
+                let y = height * 0.45 - row_height * (i as f32 + 0.5);
+                let (marker, color) = match unlocked.0.get(&achievement.id) {
+                    Some(_) => ("[x]", active.theme.tile_color(2)),
+                    None => ("[ ]", active.theme.placeholder),
+                };
+
+                builder.spawn(Text2dBundle {
+                    text: Text::from_section(
+                        format!(
+                            "{} {} - {}",
+                            marker, achievement.name, achievement.description
+                        ),
+                        TextStyle {
+                            font_size,
+                            color,
+                            ..default()
+                        },
+                    )
+                    .with_alignment(TextAlignment::Center),
+                    transform: Transform::from_xyz(0.0, y, 1.0),
+                    ..default()
+                });
+            }
+        });
+}
+
+fn despawn_gallery(mut commands: Commands, galleries: Query<Entity, With<Gallery>>) {
+    for entity in galleries.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Grid;
+
+    /// What a game had done when its achievements were checked.
+    struct Played {
+        size: u8,
+        max_tile: u32,
+        moves: u32,
+        undos: u32,
+        games_played: u32,
+        largest_merge: u32,
+        won: bool,
+    }
+
+    impl Default for Played {
+        fn default() -> Self {
+            Played {
+                size: 4,
+                max_tile: 8,
+                moves: 10,
+                undos: 0,
+                games_played: 0,
+                largest_merge: 0,
+                won: false,
+            }
+        }
+    }
+
+    impl Played {
+        fn meets(&self, condition: &Condition) -> bool {
+            let mut game = Game::new(self.size, 0);
+            game.grid = Grid::new(self.size);
+            game.grid.set((0, 0), Some(self.max_tile));
+            let stats = GameStats {
+                moves: self.moves,
+                undos: self.undos,
+                largest_tile: self.max_tile,
+                ..default()
+            };
+            let lifetime = LifetimeStats {
+                games_played: self.games_played,
+                ..default()
+            };
+            condition.is_met(&game, &stats, &lifetime, self.largest_merge, self.won)
+        }
+    }
+
+    #[test]
+    fn conditions_are_met_by_the_right_games() {
+        let speedrun = Condition::ReachTileWithinMoves {
+            tile: 1024,
+            moves: 299,
+        };
+        let cases = [
+            (
+                Condition::ReachTile(512),
+                Played {
+                    max_tile: 512,
+                    ..default()
+                },
+                true,
+            ),
+            (
+                Condition::ReachTile(512),
+                Played {
+                    max_tile: 256,
+                    ..default()
+                },
+                false,
+            ),
+            (
+                Condition::ReachTileWithoutUndo(2048),
+                Played {
+                    max_tile: 2048,
+                    moves: 900,
+                    won: true,
+                    ..default()
+                },
+                true,
+            ),
+            (
+                Condition::ReachTileWithoutUndo(2048),
+                Played {
+                    max_tile: 2048,
+                    moves: 900,
+                    undos: 1,
+                    won: true,
+                    ..default()
+                },
+                false,
+            ),
+            (
+                Condition::ReachTileWithoutUndo(2048),
+                Played {
+                    max_tile: 1024,
+                    moves: 900,
+                    ..default()
+                },
+                false,
+            ),
+            (
+                speedrun.clone(),
+                Played {
+                    max_tile: 1024,
+                    moves: 299,
+                    ..default()
+                },
+                true,
+            ),
+            (
+                speedrun.clone(),
+                Played {
+                    max_tile: 1024,
+                    moves: 300,
+                    ..default()
+                },
+                false,
+            ),
+            (
+                speedrun,
+                Played {
+                    max_tile: 512,
+                    moves: 100,
+                    ..default()
+                },
+                false,
+            ),
+            (
+                Condition::MergesInOneMove(3),
+                Played {
+                    largest_merge: 3,
+                    ..default()
+                },
+                true,
+            ),
+            (
+                Condition::MergesInOneMove(3),
+                Played {
+                    largest_merge: 2,
+                    ..default()
+                },
+                false,
+            ),
+            (
+                Condition::WinOnBoardSize(3),
+                Played {
+                    size: 3,
+                    max_tile: 2048,
+                    won: true,
+                    ..default()
+                },
+                true,
+            ),
+            (
+                Condition::WinOnBoardSize(3),
+                Played {
+                    size: 4,
+                    max_tile: 2048,
+                    won: true,
+                    ..default()
+                },
+                false,
+            ),
+            (
+                Condition::WinOnBoardSize(3),
+                Played {
+                    size: 3,
+                    max_tile: 2048,
+                    won: false,
+                    ..default()
+                },
+                false,
+            ),
+            (
+                Condition::GamesPlayed(100),
+                Played {
+                    games_played: 100,
+                    ..default()
+                },
+                true,
+            ),
+            (
+                Condition::GamesPlayed(100),
+                Played {
+                    games_played: 99,
+                    ..default()
+                },
+                false,
+            ),
+        ];
+
+        for (i, (condition, played, met)) in cases.iter().enumerate() {
+            assert_eq!(played.meets(condition), *met, "case {i}: {condition:?}");
+        }
+    }
+
+    #[test]
+    fn shipped_achievements_parse() {
+        let bytes = include_bytes!("../assets/achievements/default.achievements.ron");
+        let achievements: Achievements = ron::de::from_bytes(bytes).unwrap();
+
+        assert_eq!(achievements.0.len(), 8);
+        assert!(achievements
+            .0
+            .iter()
+            .any(|achievement| achievement.condition == Condition::ReachTileWithoutUndo(2048)));
+    }
+}
@@ -0,0 +1,141 @@
+//! Plays boxes in a terminal, using the same game core and seeded RNG as the
+//! Bevy app. Handy over SSH and for poking at the rules without a window.
+//!
+//! Arrow keys slide, `u`/backspace undoes, `h` hints, `r` restarts, `e`
+//! exports the board as notation and SVG, `q` quits.
+
+use boxes::board::Board;
+use boxes::cli::Options;
+use boxes::export;
+use boxes::game::{Direction, Game, Rules};
+use boxes::theme::Theme;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEventKind},
+    execute, queue,
+    style::Print,
+    terminal::{self, ClearType},
+};
+use std::io::{self, Write};
+
+const CELL_WIDTH: usize = 6;
+/// The window size the exported SVG is laid out for, as there's no window.
+const EXPORT_SIZE: f32 = 400.0;
+
+fn main() -> io::Result<()> {
+    let options = Options::from_args();
+    let mut game = options
+        .game(Rules::default())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+
+    let result = run(&mut stdout, &mut game);
+
+    execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn run(stdout: &mut impl Write, game: &mut Game) -> io::Result<()> {
+    let mut message = None;
+    loop {
+        draw(stdout, game, message.take())?;
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        let direction = match key.code {
+            KeyCode::Up => Direction::Up,
+            KeyCode::Down => Direction::Down,
+            KeyCode::Left => Direction::Left,
+            KeyCode::Right => Direction::Right,
+            KeyCode::Char('u') | KeyCode::Backspace => {
+                game.undo();
+                continue;
+            }
+            KeyCode::Char('h') => {
+                game.show_hint();
+                continue;
+            }
+            KeyCode::Char('r') => {
+                game.restart();
+                continue;
+            }
+            KeyCode::Char('e') => {
+                let board = Board::new(game.grid.size(), EXPORT_SIZE, EXPORT_SIZE);
+                message = Some(match export::export(game, &board, &Theme::default()) {
+                    Ok((notation, svg)) => {
+                        format!("exported {} and {}", notation.display(), svg.display())
+                    }
+                    Err(err) => format!("couldn't export the board: {}", err),
+                });
+                continue;
+            }
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            _ => continue,
+        };
+        game.play(direction);
+    }
+}
+
+fn draw(stdout: &mut impl Write, game: &Game, message: Option<String>) -> io::Result<()> {
+    let size = game.grid.size();
+    let border = format!(
+        "+{}",
+        format!("{}+", "-".repeat(CELL_WIDTH)).repeat(usize::from(size))
+    );
+
+    queue!(
+        stdout,
+        terminal::Clear(ClearType::All),
+        cursor::MoveTo(0, 0),
+        Print(format!(
+            "boxes  score {}  moves {}  seed {}",
+            game.score,
+            game.moves,
+            game.seed()
+        )),
+        cursor::MoveToNextLine(1),
+        Print(&border),
+        cursor::MoveToNextLine(1),
+    )?;
+
+    // Row 0 is the bottom of the board, so draw from the top row down.
+    for y in (0..size).rev() {
+        let row: String = (0..size)
+            .map(|x| match game.grid.get((x, y)) {
+                Some(value) => format!("{:>width$} |", value, width = CELL_WIDTH - 1),
+                None => format!("{} |", " ".repeat(CELL_WIDTH - 1)),
+            })
+            .collect();
+        queue!(
+            stdout,
+            Print(format!("|{}", row)),
+            cursor::MoveToNextLine(1),
+            Print(&border),
+            cursor::MoveToNextLine(1),
+        )?;
+    }
+
+    let status = if let Some(message) = message {
+        message
+    } else if game.won {
+        "you win! (r to restart)".to_string()
+    } else if game.over {
+        "game over (r to restart)".to_string()
+    } else if let Some(hint) = game.hint {
+        format!("hint: {}", hint.name())
+    } else {
+        "arrows slide, u undo, h hint, r restart, e export, q quit".to_string()
+    };
+    queue!(stdout, Print(status), cursor::MoveToNextLine(1))?;
+
+    stdout.flush()
+}
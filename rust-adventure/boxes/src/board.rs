@@ -1,13 +1,19 @@
 use crate::colors;
 use bevy::prelude::*;
 
-const TILE_SIZE: f32 = 40.0;
-const TILE_SPACER: f32 = 10.0;
+/// Default spacing between tiles, as a fraction of the tile size.
+const TILE_SPACER_RATIO: f32 = 0.25;
+/// How much of the window's smaller dimension the board may take up.
+const BOARD_WINDOW_FILL: f32 = 0.9;
 
 #[derive(Component)]
 pub struct Board {
     pub size: u8,
     pub physical_size: f32,
+    pub tile_size: f32,
+    pub tile_spacer: f32,
+    spacer_ratio: f32,
+    window_size: Vec2,
 }
 
 #[derive(Component)]
@@ -16,31 +22,87 @@ pub struct Position {
     pub y: u8,
 }
 
-impl Board {
-    pub fn new(size: u8) -> Self {
-        let physical_size = f32::from(size) * TILE_SIZE + f32::from(size + 1) * TILE_SPACER;
+#[derive(Component)]
+pub struct TilePlaceholder;
 
-        Board {
+impl Board {
+    pub fn new(size: u8, window_width: f32, window_height: f32) -> Self {
+        let mut board = Board {
             size,
-            physical_size,
-        }
+            physical_size: 0.0,
+            tile_size: 0.0,
+            tile_spacer: 0.0,
+            spacer_ratio: TILE_SPACER_RATIO,
+            window_size: Vec2::new(window_width, window_height),
+        };
+        board.layout();
+        board
+    }
+
+    pub fn resize(&mut self, window_width: f32, window_height: f32) {
+        self.window_size = Vec2::new(window_width, window_height);
+        self.layout();
+    }
+
+    /// Sets the spacing between tiles, as a fraction of the tile size.
+    pub fn set_spacing(&mut self, spacer_ratio: f32) {
+        self.spacer_ratio = spacer_ratio.max(0.0);
+        self.layout();
+    }
+
+    /// Recomputes the tile size so that the whole board fits in the window.
+    fn layout(&mut self) {
+        let available = self.window_size.min_element() * BOARD_WINDOW_FILL;
+        let size = f32::from(self.size);
+
+        self.tile_size = available / (size + (size + 1.0) * self.spacer_ratio);
+        self.tile_spacer = self.tile_size * self.spacer_ratio;
+        self.physical_size = size * self.tile_size + (size + 1.0) * self.tile_spacer;
+    }
+
+    pub fn text_size(&self) -> f32 {
+        self.tile_size
     }
 
     pub fn cell_position_to_physical(&self, pos: u8) -> f32 {
-        let offset = -self.physical_size / 2.0 + 0.5 * TILE_SIZE;
-        offset + f32::from(pos) * TILE_SIZE + f32::from(pos + 1) * TILE_SPACER
+        let offset = -self.physical_size / 2.0 + 0.5 * self.tile_size;
+        offset + f32::from(pos) * self.tile_size + f32::from(pos + 1) * self.tile_spacer
     }
 
-    pub fn make_board_sprite(&self) -> SpriteBundle {
-        let sprite_size = Vec2 {
-            x: self.physical_size,
-            y: self.physical_size,
+    /// The cell under a point in board space, if it's on a tile rather than
+    /// a gap or off the board.
+    pub fn physical_to_cell(&self, point: Vec2) -> Option<(u8, u8)> {
+        let step = self.tile_size + self.tile_spacer;
+        let axis = |value: f32| {
+            let offset = value + self.physical_size / 2.0 - self.tile_spacer;
+            let cell = (offset / step).floor();
+            let on_tile = offset - cell * step < self.tile_size;
+            (offset >= 0.0 && cell < f32::from(self.size) && on_tile).then_some(cell as u8)
         };
+        Some((axis(point.x)?, axis(point.y)?))
+    }
+
+    pub fn board_sprite_size(&self) -> Vec2 {
+        Vec2::splat(self.physical_size)
+    }
 
+    pub fn tile_sprite_size(&self) -> Vec2 {
+        Vec2::splat(self.tile_size)
+    }
+
+    pub fn tile_translation(&self, tile: &Position, z: f32) -> Vec3 {
+        Vec3::new(
+            self.cell_position_to_physical(tile.x),
+            self.cell_position_to_physical(tile.y),
+            z,
+        )
+    }
+
+    pub fn make_board_sprite(&self) -> SpriteBundle {
         SpriteBundle {
             sprite: Sprite {
                 color: colors::BOARD,
-                custom_size: Some(sprite_size),
+                custom_size: Some(self.board_sprite_size()),
                 ..default()
             },
             ..default()
@@ -51,14 +113,10 @@ impl Board {
         SpriteBundle {
             sprite: Sprite {
                 color,
-                custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                custom_size: Some(self.tile_sprite_size()),
                 ..default()
             },
-            transform: Transform::from_xyz(
-                self.cell_position_to_physical(tile.x),
-                self.cell_position_to_physical(tile.y),
-                1.0,
-            ),
+            transform: Transform::from_translation(self.tile_translation(tile, 1.0)),
             ..default()
         }
     }
@@ -0,0 +1,64 @@
+use crate::game::{Game, Rules};
+use crate::notation::BoardState;
+use std::{env, fs, path::PathBuf};
+
+/// Command line options shared by the Bevy and terminal front-ends.
+pub struct Options {
+    pub seed: u64,
+    pub size: u8,
+    /// A file holding a position in `notation` to start from.
+    pub load: Option<PathBuf>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            seed: rand::random(),
+            size: 4,
+            load: None,
+        }
+    }
+}
+
+impl Options {
+    /// Reads `--seed <n>`, `--size <n>` and `--load <file>`, ignoring anything
+    /// it doesn't know.
+    pub fn from_args() -> Self {
+        let mut options = Options::default();
+        let mut args = env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => {
+                    if let Some(seed) = args.next().and_then(|value| value.parse().ok()) {
+                        options.seed = seed;
+                    }
+                }
+                "--size" => {
+                    if let Some(size) = args.next().and_then(|value| value.parse().ok()) {
+                        options.size = u8::max(size, 2);
+                    }
+                }
+                "--load" => options.load = args.next().map(PathBuf::from),
+                _ => {}
+            }
+        }
+
+        options
+    }
+
+    /// The game to play: the `--load`ed position if there is one, otherwise a
+    /// new one from the seed and size.
+    pub fn game(&self, rules: Rules) -> Result<Game, String> {
+        let Some(path) = &self.load else {
+            return Ok(Game::with_rules(self.size, self.seed, rules));
+        };
+
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("couldn't read {}: {}", path.display(), err))?;
+        let state: BoardState = text
+            .parse()
+            .map_err(|err| format!("couldn't load {}: {}", path.display(), err))?;
+        Ok(state.into_game(rules))
+    }
+}
@@ -1,5 +1,14 @@
 use bevy::prelude::Color;
 
+const fn lcha(lightness: f32, chroma: f32, hue: f32) -> Color {
+    Color::Lcha {
+        lightness,
+        chroma,
+        hue,
+        alpha: 1.0,
+    }
+}
+
 pub const BOARD: Color = Color::Lcha {
     lightness: 0.06,
     chroma: 0.088,
@@ -20,3 +29,174 @@ pub const TILE: Color = Color::Lcha {
     hue: 315.0,
     alpha: 1.0,
 };
+
+pub const HIGH_CONTRAST_BOARD: Color = lcha(0.0, 0.0, 0.0);
+pub const HIGH_CONTRAST_PLACEHOLDER: Color = lcha(0.4, 0.0, 0.0);
+
+/// Tiles for 2 up to 2048, alternating blue and orange as they get lighter.
+pub const DEUTERANOPIA_TILES: [Color; 11] = [
+    lcha(0.58, 0.4, 255.0),
+    lcha(0.62, 0.4, 65.0),
+    lcha(0.66, 0.4, 255.0),
+    lcha(0.7, 0.4, 65.0),
+    lcha(0.74, 0.4, 255.0),
+    lcha(0.77, 0.4, 65.0),
+    lcha(0.81, 0.4, 255.0),
+    lcha(0.85, 0.4, 65.0),
+    lcha(0.89, 0.4, 255.0),
+    lcha(0.93, 0.4, 65.0),
+    lcha(0.97, 0.4, 255.0),
+];
+
+/// Tiles for 2 up to 2048, alternating blue and yellow as they get lighter.
+pub const PROTANOPIA_TILES: [Color; 11] = [
+    lcha(0.58, 0.4, 250.0),
+    lcha(0.62, 0.4, 80.0),
+    lcha(0.66, 0.4, 250.0),
+    lcha(0.7, 0.4, 80.0),
+    lcha(0.74, 0.4, 250.0),
+    lcha(0.77, 0.4, 80.0),
+    lcha(0.81, 0.4, 250.0),
+    lcha(0.85, 0.4, 80.0),
+    lcha(0.89, 0.4, 250.0),
+    lcha(0.93, 0.4, 80.0),
+    lcha(0.97, 0.4, 250.0),
+];
+
+/// Tiles for 2 up to 2048, alternating red and teal as they get lighter.
+pub const TRITANOPIA_TILES: [Color; 11] = [
+    lcha(0.58, 0.4, 15.0),
+    lcha(0.62, 0.4, 195.0),
+    lcha(0.66, 0.4, 15.0),
+    lcha(0.7, 0.4, 195.0),
+    lcha(0.74, 0.4, 15.0),
+    lcha(0.77, 0.4, 195.0),
+    lcha(0.81, 0.4, 15.0),
+    lcha(0.85, 0.4, 195.0),
+    lcha(0.89, 0.4, 15.0),
+    lcha(0.93, 0.4, 195.0),
+    lcha(0.97, 0.4, 15.0),
+];
+
+/// Bright tiles for 2 up to 2048, meant for black text.
+pub const HIGH_CONTRAST_TILES: [Color; 11] = [
+    lcha(0.99, 0.0, 0.0),
+    lcha(0.88, 0.45, 95.0),
+    lcha(0.88, 0.45, 200.0),
+    lcha(0.88, 0.45, 330.0),
+    lcha(0.88, 0.45, 140.0),
+    lcha(0.88, 0.45, 40.0),
+    lcha(0.99, 0.0, 0.0),
+    lcha(0.89, 0.45, 95.0),
+    lcha(0.89, 0.45, 200.0),
+    lcha(0.89, 0.45, 330.0),
+    lcha(0.89, 0.45, 140.0),
+];
+
+/// Relative luminance as defined by WCAG 2.
+pub fn relative_luminance(color: Color) -> f32 {
+    let [red, green, blue, _] = color.as_linear_rgba_f32();
+    0.2126 * red + 0.7152 * green + 0.0722 * blue
+}
+
+/// WCAG 2 contrast ratio, from 1.0 (none) to 21.0 (black on white).
+pub fn contrast_ratio(a: Color, b: Color) -> f32 {
+    let (a, b) = (relative_luminance(a), relative_luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Machado et al. (2009) full-severity simulation matrices, applied to
+    /// linear RGB.
+    const PROTANOPIA: [[f32; 3]; 3] = [
+        [0.152286, 1.052583, -0.204868],
+        [0.114503, 0.786281, 0.099216],
+        [-0.003882, -0.048116, 1.051998],
+    ];
+    const DEUTERANOPIA: [[f32; 3]; 3] = [
+        [0.367322, 0.860646, -0.227968],
+        [0.280085, 0.672501, 0.047413],
+        [-0.011820, 0.042940, 0.968881],
+    ];
+    const TRITANOPIA: [[f32; 3]; 3] = [
+        [1.255528, -0.076749, -0.178779],
+        [-0.078411, 0.930809, 0.147602],
+        [0.004733, 0.691367, 0.303900],
+    ];
+
+    /// How the colour looks with the given deficiency, in non-linear sRGB.
+    fn simulate(color: Color, matrix: &[[f32; 3]; 3]) -> [f32; 3] {
+        let [red, green, blue, alpha] = color.as_linear_rgba_f32();
+        let simulated =
+            matrix.map(|row| (row[0] * red + row[1] * green + row[2] * blue).clamp(0.0, 1.0));
+        let [red, green, blue, _] =
+            Color::rgba_linear(simulated[0], simulated[1], simulated[2], alpha).as_rgba_f32();
+        [red, green, blue]
+    }
+
+    fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+        a.iter()
+            .zip(b.iter())
+            .map(|(a, b)| (a - b).powi(2))
+            .sum::<f32>()
+            .sqrt()
+    }
+
+    fn assert_distinguishable(tiles: &[Color], matrix: &[[f32; 3]; 3]) {
+        for (i, a) in tiles.iter().enumerate() {
+            for (j, b) in tiles.iter().enumerate().skip(i + 1) {
+                let apart = distance(simulate(*a, matrix), simulate(*b, matrix));
+                let needed = if j == i + 1 { 0.25 } else { 0.08 };
+                assert!(
+                    apart >= needed,
+                    "tiles {} and {} are only {} apart",
+                    i,
+                    j,
+                    apart
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn contrast_ratio_bounds() {
+        assert!((contrast_ratio(Color::BLACK, Color::WHITE) - 21.0).abs() < 0.01);
+        assert!((contrast_ratio(TILE, TILE) - 1.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn default_colors_have_enough_contrast() {
+        assert!(contrast_ratio(TILE, Color::BLACK) >= 4.5);
+        assert!(contrast_ratio(TILE, BOARD) >= 3.0);
+        assert!(contrast_ratio(TILE_PLACEHOLDER, BOARD) >= 3.0);
+    }
+
+    #[test]
+    fn colour_blind_tiles_are_readable() {
+        for tiles in [DEUTERANOPIA_TILES, PROTANOPIA_TILES, TRITANOPIA_TILES] {
+            for tile in tiles {
+                assert!(contrast_ratio(tile, Color::BLACK) >= 4.5);
+                assert!(contrast_ratio(tile, BOARD) >= 3.0);
+            }
+        }
+    }
+
+    #[test]
+    fn colour_blind_tiles_are_distinguishable() {
+        assert_distinguishable(&DEUTERANOPIA_TILES, &DEUTERANOPIA);
+        assert_distinguishable(&PROTANOPIA_TILES, &PROTANOPIA);
+        assert_distinguishable(&TRITANOPIA_TILES, &TRITANOPIA);
+    }
+
+    #[test]
+    fn high_contrast_meets_aaa() {
+        for tile in HIGH_CONTRAST_TILES {
+            assert!(contrast_ratio(tile, Color::BLACK) >= 7.0);
+            assert!(contrast_ratio(tile, HIGH_CONTRAST_BOARD) >= 7.0);
+        }
+        assert!(contrast_ratio(HIGH_CONTRAST_PLACEHOLDER, HIGH_CONTRAST_BOARD) >= 3.0);
+    }
+}
@@ -0,0 +1,80 @@
+use crate::game::{Direction, Mode};
+use bevy::prelude::*;
+
+/// Something the player asked for. The keyboard is translated into these,
+/// and a host app can send them itself instead.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlayerAction {
+    Slide(Direction),
+    Undo,
+    Hint,
+    Restart,
+}
+
+#[derive(Event)]
+pub struct GameStarted;
+
+/// The grid was changed outside of normal play, so the tile entities need
+/// rebuilding from it.
+#[derive(Event)]
+pub struct BoardEdited;
+
+#[derive(Event)]
+pub struct MoveMade {
+    pub direction: Direction,
+    pub merges: u32,
+    pub score_gained: u32,
+}
+
+/// A move was attempted in a direction where nothing can slide.
+#[derive(Event)]
+pub struct MoveBlocked {
+    pub direction: Direction,
+}
+
+#[derive(Event)]
+pub struct GameEnded {
+    pub won: bool,
+    /// Restarted before it was over. It still counts as a loss in the stats,
+    /// but gets no game-over sound and isn't offered to the leaderboard.
+    pub abandoned: bool,
+    pub score: u32,
+    pub largest_tile: u32,
+    pub moves: u32,
+    pub seed: u64,
+    pub mode: Mode,
+}
+
+#[derive(Event)]
+pub struct UndoUsed;
+
+#[derive(Event)]
+pub struct HintUsed;
+
+#[derive(Event)]
+pub struct TileSpawned {
+    pub entity: Entity,
+    pub cell: (u8, u8),
+    pub value: u32,
+}
+
+#[derive(Event)]
+pub struct TileMoved {
+    pub entity: Entity,
+    pub from: (u8, u8),
+    pub to: (u8, u8),
+}
+
+/// `entity` absorbed `absorbed` and now holds `value`.
+#[derive(Event)]
+pub struct TileMerged {
+    pub entity: Entity,
+    pub absorbed: Entity,
+    pub cell: (u8, u8),
+    pub value: u32,
+}
+
+#[derive(Event)]
+pub struct TileRemoved {
+    pub entity: Entity,
+}
@@ -0,0 +1,144 @@
+use crate::board::{Board, Position};
+use crate::game::{Game, Grid};
+use crate::notation::BoardState;
+use crate::theme::{ActiveTheme, Theme};
+use crate::{storage, CurrentGame};
+use bevy::prelude::*;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fs, io};
+
+/// Saves the board as notation and SVG under the data directory with `E`.
+pub struct ExportPlugin;
+
+impl Plugin for ExportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, export_board);
+    }
+}
+
+fn export_board(
+    keys: Res<Input<KeyCode>>,
+    game: Res<CurrentGame>,
+    boards: Query<&Board>,
+    active: Res<ActiveTheme>,
+) {
+    if !keys.just_pressed(KeyCode::E) {
+        return;
+    }
+
+    match export(&game, boards.single(), &active.theme) {
+        Ok((notation, svg)) => info!("exported {} and {}", notation.display(), svg.display()),
+        Err(err) => warn!("couldn't export the board: {}", err),
+    }
+}
+
+/// Writes `game` as notation and as an SVG drawn with `board` and `theme`,
+/// under `exports` in the data directory. Returns the two files' paths.
+pub fn export(game: &Game, board: &Board, theme: &Theme) -> io::Result<(PathBuf, PathBuf)> {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_millis())
+        .unwrap_or(0);
+    let dir = storage::data_dir().join("exports");
+    fs::create_dir_all(&dir)?;
+
+    // Two exports in the same millisecond get numbered rather than overwritten.
+    let (notation, svg) = (1..)
+        .map(|n| {
+            let name = match n {
+                1 => format!("board-{stamp}"),
+                n => format!("board-{stamp}-{n}"),
+            };
+            (
+                dir.join(format!("{name}.txt")),
+                dir.join(format!("{name}.svg")),
+            )
+        })
+        .find(|(notation, svg)| !notation.exists() && !svg.exists())
+        .expect("some number is free");
+
+    fs::write(&notation, BoardState::of(game).to_string())?;
+    fs::write(&svg, board_svg(board, theme, &game.grid))?;
+    Ok((notation, svg))
+}
+
+/// Draws `grid` the way the app would: same layout as `board`, same colours
+/// as `theme`. The picture is cropped to the board itself.
+pub fn board_svg(board: &Board, theme: &Theme, grid: &Grid) -> String {
+    let size = board.physical_size;
+    let radius = theme.corner_radius.clamp(0.0, 0.5) * board.tile_size;
+    let mut svg = String::new();
+
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{size:.0}" height="{size:.0}" viewBox="0 0 {size} {size}">"#
+    );
+    let _ = writeln!(
+        svg,
+        r#"  <rect width="{size}" height="{size}" {}/>"#,
+        fill(theme.board)
+    );
+
+    for (x, y) in grid.cells() {
+        // Bevy's y axis points up and its origin is the board's centre.
+        let centre = board.tile_translation(&Position { x, y }, 0.0);
+        let left = centre.x + size / 2.0 - board.tile_size / 2.0;
+        let top = size / 2.0 - centre.y - board.tile_size / 2.0;
+        let value = grid.get((x, y));
+        let color = value.map_or(theme.placeholder, |value| theme.tile_color(value));
+
+        let _ = writeln!(
+            svg,
+            r#"  <rect x="{left}" y="{top}" width="{tile}" height="{tile}" rx="{radius}" {}/>"#,
+            fill(color),
+            tile = board.tile_size,
+        );
+        if let Some(value) = value {
+            let _ = writeln!(
+                svg,
+                r#"  <text x="{}" y="{}" font-family="sans-serif" font-size="{}" text-anchor="middle" dominant-baseline="central" {}>{value}</text>"#,
+                left + board.tile_size / 2.0,
+                top + board.tile_size / 2.0,
+                board.text_size(),
+                fill(theme.text),
+            );
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn fill(color: Color) -> String {
+    let [r, g, b, a] = color.as_rgba_u8();
+    if a == u8::MAX {
+        format!(r##"fill="#{r:02x}{g:02x}{b:02x}""##)
+    } else {
+        format!(
+            r##"fill="#{r:02x}{g:02x}{b:02x}" fill-opacity="{:.3}""##,
+            f32::from(a) / 255.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_every_cell_and_tile() {
+        let mut grid = Grid::new(4);
+        grid.set((0, 0), Some(2));
+        grid.set((3, 3), Some(2048));
+        let board = Board::new(4, 400.0, 400.0);
+
+        let svg = board_svg(&board, &Theme::default(), &grid);
+
+        assert_eq!(svg.matches("<rect").count(), 1 + 16);
+        assert!(svg.contains(">2</text>"));
+        assert!(svg.contains(">2048</text>"));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+}
@@ -0,0 +1,457 @@
+use itertools::Itertools;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+pub const WINNING_TILE: u32 = 2048;
+
+/// How a game got its starting position.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum Mode {
+    /// Dealt from a seed, like every new game.
+    #[default]
+    Classic,
+    /// Picked up from a given position, e.g. one loaded with `--load`, or
+    /// edited by hand since it was dealt.
+    Custom,
+}
+
+/// The knobs that change how a game plays out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rules {
+    /// The tile that wins the game.
+    pub winning_tile: u32,
+    /// How likely a new tile is to be a 4 rather than a 2.
+    pub four_chance: f64,
+    /// Whether the last move can be taken back.
+    pub undo: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            winning_tile: WINNING_TILE,
+            four_chance: 0.1,
+            undo: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Direction::Up => "up",
+            Direction::Down => "down",
+            Direction::Left => "left",
+            Direction::Right => "right",
+        }
+    }
+}
+
+/// A tile that slid from one cell to another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Slide {
+    pub from: (u8, u8),
+    pub to: (u8, u8),
+}
+
+/// A tile that slid into another one and got absorbed by it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Merge {
+    pub from: (u8, u8),
+    pub into: (u8, u8),
+    pub value: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SlideOutcome {
+    pub slides: Vec<Slide>,
+    pub merges: Vec<Merge>,
+    pub score: u32,
+}
+
+impl SlideOutcome {
+    pub fn changed(&self) -> bool {
+        !self.slides.is_empty() || !self.merges.is_empty()
+    }
+}
+
+/// The board as plain values, `y == 0` being the bottom row.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Grid {
+    size: u8,
+    cells: Vec<Option<u32>>,
+}
+
+impl Grid {
+    pub fn new(size: u8) -> Self {
+        Grid {
+            size,
+            cells: vec![None; usize::from(size) * usize::from(size)],
+        }
+    }
+
+    pub fn size(&self) -> u8 {
+        self.size
+    }
+
+    fn index(&self, (x, y): (u8, u8)) -> usize {
+        usize::from(y) * usize::from(self.size) + usize::from(x)
+    }
+
+    pub fn get(&self, cell: (u8, u8)) -> Option<u32> {
+        self.cells[self.index(cell)]
+    }
+
+    pub fn set(&mut self, cell: (u8, u8), value: Option<u32>) {
+        let index = self.index(cell);
+        self.cells[index] = value;
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = (u8, u8)> {
+        (0..self.size).cartesian_product(0..self.size)
+    }
+
+    pub fn tiles(&self) -> impl Iterator<Item = ((u8, u8), u32)> + '_ {
+        self.cells()
+            .filter_map(|cell| self.get(cell).map(|value| (cell, value)))
+    }
+
+    pub fn empty_cells(&self) -> Vec<(u8, u8)> {
+        self.cells()
+            .filter(|cell| self.get(*cell).is_none())
+            .collect()
+    }
+
+    pub fn max_tile(&self) -> u32 {
+        self.tiles().map(|(_, value)| value).max().unwrap_or(0)
+    }
+
+    /// Places a new tile in a random empty cell, returning where it went.
+    pub fn spawn_random(
+        &mut self,
+        rng: &mut impl Rng,
+        four_chance: f64,
+    ) -> Option<((u8, u8), u32)> {
+        let cell = *self.empty_cells().choose(rng)?;
+        let value = if rng.gen_bool(1.0 - four_chance) {
+            2
+        } else {
+            4
+        };
+        self.set(cell, Some(value));
+        Some((cell, value))
+    }
+
+    /// The cells of every line along `direction`, each starting at the edge
+    /// the tiles slide towards.
+    fn lines(&self, direction: Direction) -> Vec<Vec<(u8, u8)>> {
+        let last = self.size - 1;
+        (0..self.size)
+            .map(|line| {
+                (0..self.size)
+                    .map(|i| match direction {
+                        Direction::Left => (i, line),
+                        Direction::Right => (last - i, line),
+                        Direction::Down => (line, i),
+                        Direction::Up => (line, last - i),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn slide(&mut self, direction: Direction) -> SlideOutcome {
+        let mut outcome = SlideOutcome::default();
+        let mut next = Grid::new(self.size);
+
+        for line in self.lines(direction) {
+            let mut write = 0;
+            let mut mergeable: Option<(u32, usize)> = None;
+
+            for (read, cell) in line.iter().enumerate() {
+                let Some(value) = self.get(*cell) else {
+                    continue;
+                };
+
+                if let Some((last_value, last_write)) = mergeable {
+                    if last_value == value {
+                        let merged = value * 2;
+                        next.set(line[last_write], Some(merged));
+                        outcome.merges.push(Merge {
+                            from: *cell,
+                            into: line[last_write],
+                            value: merged,
+                        });
+                        outcome.score += merged;
+                        mergeable = None;
+                        continue;
+                    }
+                }
+
+                next.set(line[write], Some(value));
+                if write != read {
+                    outcome.slides.push(Slide {
+                        from: *cell,
+                        to: line[write],
+                    });
+                }
+                mergeable = Some((value, write));
+                write += 1;
+            }
+        }
+
+        *self = next;
+        outcome
+    }
+
+    pub fn can_slide(&self, direction: Direction) -> bool {
+        self.clone().slide(direction).changed()
+    }
+
+    pub fn can_move(&self) -> bool {
+        Direction::ALL
+            .iter()
+            .any(|direction| self.can_slide(*direction))
+    }
+
+    /// A greedy suggestion: the move scoring the most, then the one leaving
+    /// the most room.
+    pub fn hint(&self) -> Option<Direction> {
+        Direction::ALL
+            .iter()
+            .filter_map(|direction| {
+                let mut grid = self.clone();
+                let outcome = grid.slide(*direction);
+                outcome
+                    .changed()
+                    .then(|| (*direction, outcome.score, grid.empty_cells().len()))
+            })
+            .max_by_key(|(_, score, empty)| (*score, *empty))
+            .map(|(direction, _, _)| direction)
+    }
+}
+
+/// What happened during one turn of `Game::play`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Turn {
+    pub direction: Direction,
+    pub outcome: SlideOutcome,
+    pub spawned: Option<((u8, u8), u32)>,
+    /// `Some(won)` when this turn ended the game.
+    pub ended: Option<bool>,
+}
+
+#[derive(Clone)]
+struct Snapshot {
+    grid: Grid,
+    score: u32,
+    rng: ChaCha8Rng,
+}
+
+/// A whole game: the grid plus the rules around it. Every random choice comes
+/// from a RNG seeded with `seed`, so a seed and a list of moves always play
+/// out the same way, whichever front-end runs them.
+#[derive(Clone)]
+pub struct Game {
+    pub grid: Grid,
+    pub score: u32,
+    pub moves: u32,
+    pub over: bool,
+    pub won: bool,
+    pub hint: Option<Direction>,
+    mode: Mode,
+    rules: Rules,
+    forced_spawn: Option<((u8, u8), u32)>,
+    seed: u64,
+    rng: ChaCha8Rng,
+    undo: Option<Snapshot>,
+}
+
+impl Game {
+    pub fn new(size: u8, seed: u64) -> Self {
+        Game::with_rules(size, seed, Rules::default())
+    }
+
+    pub fn with_rules(size: u8, seed: u64, rules: Rules) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut grid = Grid::new(size);
+        grid.spawn_random(&mut rng, rules.four_chance);
+        grid.spawn_random(&mut rng, rules.four_chance);
+
+        Game {
+            grid,
+            score: 0,
+            moves: 0,
+            over: false,
+            won: false,
+            hint: None,
+            mode: Mode::Classic,
+            rules,
+            forced_spawn: None,
+            seed,
+            rng,
+            undo: None,
+        }
+    }
+
+    /// Picks up from a position, say one loaded from a bug report. The RNG
+    /// carries on from `rng_position` in `seed`'s stream, so spawns follow the
+    /// original game from there.
+    pub fn resume(grid: Grid, score: u32, seed: u64, rng_position: u128, rules: Rules) -> Self {
+        let mut game = Game::with_rules(grid.size(), seed, rules);
+        game.rng.set_word_pos(rng_position);
+        game.mode = Mode::Custom;
+        game.won = grid.max_tile() >= rules.winning_tile;
+        game.over = game.won || !grid.can_move();
+        game.grid = grid;
+        game.score = score;
+        game
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// How far into `seed`'s stream the RNG has got.
+    pub fn rng_position(&self) -> u128 {
+        self.rng.get_word_pos()
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    /// Slides the tiles and spawns a new one, or returns `None` when the game
+    /// is over or nothing could slide that way.
+    pub fn play(&mut self, direction: Direction) -> Option<Turn> {
+        if self.over || !self.grid.can_slide(direction) {
+            return None;
+        }
+
+        self.undo = Some(Snapshot {
+            grid: self.grid.clone(),
+            score: self.score,
+            rng: self.rng.clone(),
+        });
+
+        let outcome = self.grid.slide(direction);
+        self.score += outcome.score;
+        self.moves += 1;
+        self.hint = None;
+        let spawned = match self.forced_spawn.take() {
+            Some((cell, value)) if self.grid.get(cell).is_none() => {
+                self.grid.set(cell, Some(value));
+                Some((cell, value))
+            }
+            _ => self
+                .grid
+                .spawn_random(&mut self.rng, self.rules.four_chance),
+        };
+
+        let ended = if !self.won && self.grid.max_tile() >= self.rules.winning_tile {
+            self.won = true;
+            Some(true)
+        } else if !self.grid.can_move() {
+            Some(false)
+        } else {
+            None
+        };
+        self.over = ended.is_some();
+
+        Some(Turn {
+            direction,
+            outcome,
+            spawned,
+            ended,
+        })
+    }
+
+    /// Takes back the last move. The RNG is rewound too, so undoing can't be
+    /// used to re-roll the next spawn. Once the game is over its result has
+    /// been sent out, so there's no going back from a win or a loss.
+    pub fn undo(&mut self) -> bool {
+        if self.over || !self.rules.undo {
+            return false;
+        }
+        let Some(snapshot) = self.undo.take() else {
+            return false;
+        };
+
+        self.grid = snapshot.grid;
+        self.score = snapshot.score;
+        self.rng = snapshot.rng;
+        self.over = false;
+        self.hint = None;
+        true
+    }
+
+    /// Changes a tile by hand, making this a `Custom` game. The edit can't be
+    /// undone, and ends the game if it leaves no moves. Like undo, it's refused
+    /// once the game is over, since the result has already been sent out.
+    pub fn set_tile(&mut self, cell: (u8, u8), value: Option<u32>) -> bool {
+        if self.over {
+            return false;
+        }
+
+        self.mode = Mode::Custom;
+        self.grid.set(cell, value);
+        self.undo = None;
+        self.hint = None;
+        self.over = !self.grid.can_move();
+        true
+    }
+
+    /// Makes the next move spawn `value` at `cell` instead of rolling for it,
+    /// as long as the cell is still empty by then. Makes this a `Custom` game.
+    pub fn force_next_spawn(&mut self, spawn: Option<((u8, u8), u32)>) {
+        self.mode = Mode::Custom;
+        self.forced_spawn = spawn;
+    }
+
+    pub fn forced_spawn(&self) -> Option<((u8, u8), u32)> {
+        self.forced_spawn
+    }
+
+    /// Throws away one draw from the RNG, changing every spawn after it. Makes
+    /// this a `Custom` game.
+    pub fn step_rng(&mut self) {
+        self.mode = Mode::Custom;
+        self.rng.next_u32();
+    }
+
+    pub fn show_hint(&mut self) -> Option<Direction> {
+        if !self.over {
+            self.hint = self.grid.hint();
+        }
+        self.hint
+    }
+
+    /// Starts over on a board of the same size and rules, seeded from this
+    /// game's RNG.
+    pub fn restart(&mut self) {
+        let seed = self.rng.gen();
+        *self = Game::with_rules(self.grid.size(), seed, self.rules);
+    }
+}
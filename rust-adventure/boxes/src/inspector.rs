@@ -0,0 +1,224 @@
+use crate::board::{Board, Position};
+use crate::events::BoardEdited;
+use crate::{CurrentGame, Points};
+use bevy::{prelude::*, window::PrimaryWindow};
+
+/// The biggest tile the editor will make.
+const MAX_TILE: u32 = 1 << 17;
+
+/// A developer overlay for setting up positions by hand, toggled with `F1`:
+///
+/// - left click raises a tile, right click lowers it and middle click clears it
+/// - shift + click forces where the next spawn goes, cycling 2, 4 and off
+/// - `N` throws away a draw from the RNG
+///
+/// Every tile is labelled with its entity and `Position` meanwhile.
+pub struct InspectorPlugin;
+
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Inspector>()
+            .add_systems(Startup, spawn_panel)
+            .add_systems(
+                Update,
+                (
+                    toggle_inspector,
+                    (edit_board, step_rng, render_panel, render_labels)
+                        .chain()
+                        .after(toggle_inspector)
+                        .run_if(inspector_open),
+                    hide_inspector.after(toggle_inspector),
+                ),
+            );
+    }
+}
+
+#[derive(Resource, Default)]
+struct Inspector {
+    open: bool,
+    rng_steps: u32,
+}
+
+#[derive(Component)]
+struct InspectorPanel;
+
+#[derive(Component)]
+struct InspectorLabel;
+
+fn inspector_open(inspector: Res<Inspector>) -> bool {
+    inspector.open
+}
+
+fn toggle_inspector(keys: Res<Input<KeyCode>>, mut inspector: ResMut<Inspector>) {
+    if keys.just_pressed(KeyCode::F1) {
+        inspector.open = !inspector.open;
+    }
+}
+
+fn spawn_panel(mut commands: Commands) {
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(5.0),
+                right: Val::Px(5.0),
+                ..default()
+            })
+            .with_background_color(Color::rgba(0.0, 0.0, 0.0, 0.7)),
+        )
+        .insert(Visibility::Hidden)
+        .insert(InspectorPanel);
+}
+
+fn edit_board(
+    mouse: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    boards: Query<&Board>,
+    mut game: ResMut<CurrentGame>,
+    mut edits: EventWriter<BoardEdited>,
+) {
+    let button = [MouseButton::Left, MouseButton::Right, MouseButton::Middle]
+        .into_iter()
+        .find(|button| mouse.just_pressed(*button));
+    let Some(button) = button else {
+        return;
+    };
+    let (camera, camera_transform) = cameras.single();
+    let Some(cell) = windows
+        .single()
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+        .and_then(|point| boards.single().physical_to_cell(point))
+    else {
+        return;
+    };
+
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        let spawn = match game.forced_spawn() {
+            Some((forced, 2)) if forced == cell => Some((cell, 4)),
+            Some((forced, _)) if forced == cell => None,
+            _ => Some((cell, 2)),
+        };
+        game.force_next_spawn(spawn);
+        return;
+    }
+
+    let value = game.grid.get(cell);
+    let value = match button {
+        MouseButton::Left => Some(value.map_or(2, |value| (value * 2).min(MAX_TILE))),
+        MouseButton::Right => value.map(|value| value / 2).filter(|value| *value >= 2),
+        _ => None,
+    };
+    if game.set_tile(cell, value) {
+        edits.send(BoardEdited);
+    }
+}
+
+fn step_rng(
+    keys: Res<Input<KeyCode>>,
+    mut inspector: ResMut<Inspector>,
+    mut game: ResMut<CurrentGame>,
+) {
+    if keys.just_pressed(KeyCode::N) {
+        game.step_rng();
+        inspector.rng_steps += 1;
+    }
+}
+
+fn render_panel(
+    inspector: Res<Inspector>,
+    game: Res<CurrentGame>,
+    mut panels: Query<(&mut Text, &mut Visibility), With<InspectorPanel>>,
+) {
+    let (mut text, mut visibility) = panels.single_mut();
+    *visibility = Visibility::Visible;
+
+    let forced = match game.forced_spawn() {
+        Some(((x, y), value)) => format!("{value} at ({x}, {y})"),
+        None => "random".to_string(),
+    };
+    text.sections[0].value = format!(
+        "INSPECTOR (F1)\n\
+         seed {}   rng steps {}\n\
+         next spawn: {}\n\
+         L/R click: raise/lower tile\n\
+         middle click: clear\n\
+         shift+click: force spawn\n\
+         N: step rng",
+        game.seed(),
+        inspector.rng_steps,
+        forced
+    );
+}
+
+/// Relabels the tiles when the inspector opens or any tile spawns, moves or
+/// goes away.
+fn render_labels(
+    mut commands: Commands,
+    inspector: Res<Inspector>,
+    boards: Query<Ref<Board>>,
+    tiles: Query<(Entity, Ref<Position>), With<Points>>,
+    mut removed: RemovedComponents<Points>,
+    labels: Query<Entity, With<InspectorLabel>>,
+) {
+    let board = boards.single();
+    let removed = removed.iter().count() > 0;
+    let moved = tiles.iter().any(|(_, pos)| pos.is_changed());
+    if !inspector.is_changed() && !board.is_changed() && !removed && !moved {
+        return;
+    }
+
+    for label in labels.iter() {
+        commands.entity(label).despawn();
+    }
+
+    let style = TextStyle {
+        font_size: (board.tile_size * 0.18).max(8.0),
+        color: Color::WHITE,
+        ..default()
+    };
+    for (entity, pos) in tiles.iter() {
+        let mut translation = board.tile_translation(&pos, 5.0);
+        translation.y += board.tile_size * 0.35;
+
+        commands
+            .spawn(Text2dBundle {
+                text: Text::from_section(
+                    format!("{:?} ({}, {})", entity, pos.x, pos.y),
+                    style.clone(),
+                )
+                .with_alignment(TextAlignment::Center),
+                transform: Transform::from_translation(translation),
+                ..default()
+            })
+            .insert(InspectorLabel);
+    }
+}
+
+fn hide_inspector(
+    mut commands: Commands,
+    inspector: Res<Inspector>,
+    mut panels: Query<&mut Visibility, With<InspectorPanel>>,
+    labels: Query<Entity, With<InspectorLabel>>,
+) {
+    if !inspector.is_changed() || inspector.open {
+        return;
+    }
+
+    for mut visibility in panels.iter_mut() {
+        *visibility = Visibility::Hidden;
+    }
+    for label in labels.iter() {
+        commands.entity(label).despawn();
+    }
+}
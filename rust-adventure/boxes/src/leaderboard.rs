@@ -0,0 +1,499 @@
+use crate::events::GameEnded;
+use crate::game::{Mode, Rules, WINNING_TILE};
+use crate::theme::ActiveTheme;
+use crate::{storage, AppState, CurrentGame};
+use bevy::{
+    input::InputSystem,
+    prelude::*,
+    sprite::Anchor,
+    window::{PrimaryWindow, ReceivedCharacter},
+};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const LEADERBOARD_FILE: &str = "leaderboard.ron";
+/// Entries kept per table.
+const TOP_N: usize = 10;
+const MAX_NAME_LENGTH: usize = 16;
+
+pub struct LeaderboardPlugin;
+
+impl Plugin for LeaderboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(storage::load::<Leaderboard>(LEADERBOARD_FILE))
+            .init_resource::<PendingEntry>()
+            .init_resource::<SelectedTable>()
+            .add_systems(Update, check_scores)
+            .add_systems(
+                PreUpdate,
+                type_name
+                    .after(InputSystem)
+                    .run_if(in_state(AppState::NameEntry)),
+            )
+            .add_systems(OnEnter(AppState::NameEntry), spawn_name_prompt)
+            .add_systems(OnExit(AppState::NameEntry), despawn::<NamePrompt>)
+            .add_systems(
+                Update,
+                (switch_tables, spawn_leaderboard_screen.after(switch_tables))
+                    .run_if(in_state(AppState::Leaderboard)),
+            )
+            .add_systems(OnExit(AppState::Leaderboard), despawn::<LeaderboardScreen>);
+    }
+}
+
+/// Which table an entry competes in. Only games with the same mode, board
+/// size and rules are comparable.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TableKey {
+    pub mode: Mode,
+    pub size: u8,
+    /// A short description of the rules, empty for the standard ones.
+    pub variant: String,
+}
+
+impl TableKey {
+    pub fn new(mode: Mode, size: u8, rules: &Rules) -> Self {
+        let standard = Rules::default();
+        let mut variant = Vec::new();
+        if rules.winning_tile != WINNING_TILE {
+            variant.push(format!("to {}", rules.winning_tile));
+        }
+        if rules.four_chance != standard.four_chance {
+            variant.push(format!("{:.0}% 4s", rules.four_chance * 100.0));
+        }
+        if !rules.undo {
+            variant.push("no undo".to_string());
+        }
+
+        TableKey {
+            mode,
+            size,
+            variant: variant.join(", "),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        let mut label = format!("{0}x{0}", self.size);
+        if self.mode == Mode::Custom {
+            label.push_str(" custom");
+        }
+        if !self.variant.is_empty() {
+            label.push_str(&format!(" ({})", self.variant));
+        }
+        label
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub name: String,
+    pub score: u32,
+    pub max_tile: u32,
+    pub moves: u32,
+    /// Seconds since the Unix epoch.
+    pub date: u64,
+    /// Replaying this seed with the same moves reproduces the game.
+    pub seed: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Table {
+    pub key: TableKey,
+    /// Best first.
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Resource, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Leaderboard {
+    pub tables: Vec<Table>,
+}
+
+impl Leaderboard {
+    pub fn table(&self, key: &TableKey) -> Option<&Table> {
+        self.tables.iter().find(|table| table.key == *key)
+    }
+
+    pub fn qualifies(&self, key: &TableKey, score: u32) -> bool {
+        score > 0
+            && self.table(key).map_or(true, |table| {
+                table.entries.len() < TOP_N || table.entries.iter().any(|entry| score > entry.score)
+            })
+    }
+
+    /// Adds `entry` in rank order, returning its 0-based rank if it made the cut.
+    pub fn insert(&mut self, key: TableKey, entry: Entry) -> Option<usize> {
+        if self.table(&key).is_none() {
+            self.tables.push(Table {
+                key: key.clone(),
+                entries: Vec::new(),
+            });
+            self.tables.sort_by(|a, b| a.key.cmp(&b.key));
+        }
+
+        let table = self.tables.iter_mut().find(|table| table.key == key);
+        let entries = &mut table.expect("table was just added").entries;
+        let rank = entries
+            .iter()
+            .position(|other| entry.score > other.score)
+            .unwrap_or(entries.len());
+        entries.insert(rank, entry);
+        entries.truncate(TOP_N);
+        (rank < TOP_N).then_some(rank)
+    }
+}
+
+/// A finished game waiting for the player's name.
+#[derive(Resource, Default)]
+struct PendingEntry {
+    entry: Option<(TableKey, Entry)>,
+    name: String,
+}
+
+/// The table shown on the leaderboard screen, as an index into `tables`.
+#[derive(Resource, Default)]
+struct SelectedTable(usize);
+
+#[derive(Component)]
+struct NamePrompt;
+
+#[derive(Component)]
+struct NameText;
+
+#[derive(Component)]
+struct LeaderboardScreen;
+
+fn check_scores(
+    mut ended: EventReader<GameEnded>,
+    game: Res<CurrentGame>,
+    leaderboard: Res<Leaderboard>,
+    mut pending: ResMut<PendingEntry>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    // Size and rules survive a restart, so they can still come from the game.
+    for game_ended in ended.iter().filter(|game_ended| !game_ended.abandoned) {
+        let key = TableKey::new(game_ended.mode, game.grid.size(), &game.rules());
+        if !leaderboard.qualifies(&key, game_ended.score) {
+            continue;
+        }
+
+        let date = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or(0);
+        pending.entry = Some((
+            key,
+            Entry {
+                name: String::new(),
+                score: game_ended.score,
+                max_tile: game_ended.largest_tile,
+                moves: game_ended.moves,
+                date,
+                seed: game_ended.seed,
+            },
+        ));
+        pending.name.clear();
+        next_state.set(AppState::NameEntry);
+    }
+}
+
+/// Reads the name being typed. The prompt owns the keyboard meanwhile, so it
+/// clears the key state before any other system can act on it.
+fn type_name(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut pending: ResMut<PendingEntry>,
+    mut leaderboard: ResMut<Leaderboard>,
+    mut selected: ResMut<SelectedTable>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut prompts: Query<&mut Text, With<NameText>>,
+) {
+    for character in characters.iter() {
+        if !character.char.is_control() && pending.name.chars().count() < MAX_NAME_LENGTH {
+            pending.name.push(character.char);
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        pending.name.pop();
+    }
+
+    if keys.just_pressed(KeyCode::Escape) {
+        pending.entry = None;
+        next_state.set(AppState::Playing);
+    } else if keys.just_pressed(KeyCode::Return) {
+        if let Some((key, mut entry)) = pending.entry.take() {
+            let name = pending.name.trim();
+            entry.name = if name.is_empty() { "anonymous" } else { name }.to_string();
+            leaderboard.insert(key.clone(), entry);
+            storage::save(LEADERBOARD_FILE, &*leaderboard);
+            selected.0 = leaderboard
+                .tables
+                .iter()
+                .position(|table| table.key == key)
+                .unwrap_or(0);
+        }
+        next_state.set(AppState::Leaderboard);
+    }
+    keys.reset_all();
+
+    for mut text in prompts.iter_mut() {
+        text.sections[1].value = format!("{}_", pending.name);
+    }
+}
+
+fn spawn_name_prompt(
+    mut commands: Commands,
+    mut characters: ResMut<Events<ReceivedCharacter>>,
+    pending: Res<PendingEntry>,
+    active: Res<ActiveTheme>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+) {
+    // Whatever was typed to end the game isn't part of the name.
+    characters.clear();
+
+    let Some((key, entry)) = &pending.entry else {
+        return;
+    };
+    let window = windows.single();
+    let text_style = TextStyle {
+        font_size: (window.height() / 14.0).max(10.0),
+        color: Color::WHITE,
+        ..default()
+    };
+
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                color: active.theme.board,
+                custom_size: Some(Vec2::new(window.width() * 0.8, window.height() * 0.5)),
+                ..default()
+            },
+            transform: Transform::from_xyz(0.0, 0.0, 10.0),
+            ..default()
+        })
+        .with_children(|builder| {
+            builder
+                .spawn(Text2dBundle {
+                    text: Text::from_sections([
+                        TextSection::new(
+                            format!(
+                                "New {} high score: {}\nEnter your name (Esc to skip)\n",
+                                key.label(),
+                                entry.score
+                            ),
+                            text_style.clone(),
+                        ),
+                        TextSection::new("_", text_style),
+                    ])
+                    .with_alignment(TextAlignment::Center),
+                    transform: Transform::from_xyz(0.0, 0.0, 1.0),
+                    ..default()
+                })
+                .insert(NameText);
+        })
+        .insert(NamePrompt);
+}
+
+fn switch_tables(
+    keys: Res<Input<KeyCode>>,
+    leaderboard: Res<Leaderboard>,
+    mut selected: ResMut<SelectedTable>,
+) {
+    let count = leaderboard.tables.len().max(1);
+    if keys.just_pressed(KeyCode::Right) {
+        selected.0 = (selected.0 + 1) % count;
+    } else if keys.just_pressed(KeyCode::Left) {
+        selected.0 = (selected.0 + count - 1) % count;
+    }
+}
+
+/// Rebuilds the screen whenever another table is picked, including on entry.
+fn spawn_leaderboard_screen(
+    mut commands: Commands,
+    leaderboard: Res<Leaderboard>,
+    selected: Res<SelectedTable>,
+    active: Res<ActiveTheme>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    screens: Query<Entity, With<LeaderboardScreen>>,
+) {
+    if !selected.is_changed() && !screens.is_empty() {
+        return;
+    }
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let window = windows.single();
+    let (width, height) = (window.width(), window.height());
+    let row_height = height * 0.8 / (TOP_N + 2) as f32;
+    let text_style = TextStyle {
+        font_size: (row_height * 0.7).max(8.0),
+        color: Color::WHITE,
+        ..default()
+    };
+
+    // Tabs across the top, the selected one highlighted.
+    let tabs: Vec<TextSection> = leaderboard
+        .tables
+        .iter()
+        .enumerate()
+        .map(|(i, table)| {
+            let color = if i == selected.0 {
+                active.theme.tile_color(2)
+            } else {
+                active.theme.placeholder
+            };
+            TextSection::new(
+                format!(" [{}] ", table.key.label()),
+                TextStyle {
+                    color,
+                    ..text_style.clone()
+                },
+            )
+        })
+        .collect();
+
+    let rows = match leaderboard.tables.get(selected.0) {
+        Some(table) => table
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(rank, entry)| {
+                format!(
+                    "{:>2}. {:<16} {:>7} {:>5} {:>5} moves  {}  seed {}",
+                    rank + 1,
+                    entry.name,
+                    entry.score,
+                    entry.max_tile,
+                    entry.moves,
+                    date(entry.date),
+                    entry.seed
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        None => "No scores yet".to_string(),
+    };
+
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                color: active.theme.board,
+                custom_size: Some(Vec2::new(width, height)),
+                ..default()
+            },
+            transform: Transform::from_xyz(0.0, 0.0, 10.0),
+            ..default()
+        })
+        .insert(LeaderboardScreen)
+        .with_children(|builder| {
+            builder.spawn(Text2dBundle {
+                text: Text::from_sections(tabs).with_alignment(TextAlignment::Center),
+                transform: Transform::from_xyz(0.0, height * 0.45 - row_height / 2.0, 1.0),
+                ..default()
+            });
+            builder.spawn(Text2dBundle {
+                text: Text::from_section(rows, text_style).with_alignment(TextAlignment::Left),
+                text_anchor: Anchor::TopCenter,
+                transform: Transform::from_xyz(0.0, height * 0.45 - row_height * 1.5, 1.0),
+                ..default()
+            });
+        });
+}
+
+fn despawn<T: Component>(mut commands: Commands, entities: Query<Entity, With<T>>) {
+    for entity in entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Formats seconds since the Unix epoch as a UTC `YYYY-MM-DD` date.
+fn date(seconds: u64) -> String {
+    // Howard Hinnant's days-to-civil algorithm.
+    let days = (seconds / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: u32) -> Entry {
+        Entry {
+            name: format!("player {score}"),
+            score,
+            max_tile: 0,
+            moves: 0,
+            date: 0,
+            seed: 0,
+        }
+    }
+
+    #[test]
+    fn keeps_the_top_entries_in_order() {
+        let key = TableKey::new(Mode::Classic, 4, &Rules::default());
+        let mut leaderboard = Leaderboard::default();
+
+        for score in 1..=TOP_N as u32 {
+            assert!(leaderboard.qualifies(&key, score * 100));
+            leaderboard.insert(key.clone(), entry(score * 100));
+        }
+        assert!(!leaderboard.qualifies(&key, 50));
+        assert!(leaderboard.qualifies(&key, 150));
+        assert_eq!(leaderboard.insert(key.clone(), entry(150)), Some(TOP_N - 1));
+
+        let scores: Vec<u32> = leaderboard
+            .table(&key)
+            .unwrap()
+            .entries
+            .iter()
+            .map(|e| e.score)
+            .collect();
+        assert_eq!(scores.len(), TOP_N);
+        assert_eq!(scores[0], TOP_N as u32 * 100);
+        assert_eq!(scores[TOP_N - 1], 150);
+    }
+
+    #[test]
+    fn separates_sizes_modes_and_rules() {
+        let rules = Rules::default();
+        let classic = TableKey::new(Mode::Classic, 4, &rules);
+        let custom = TableKey::new(Mode::Custom, 4, &rules);
+        let small = TableKey::new(Mode::Classic, 3, &rules);
+        let no_undo = TableKey::new(
+            Mode::Classic,
+            4,
+            &Rules {
+                undo: false,
+                ..rules
+            },
+        );
+
+        let mut leaderboard = Leaderboard::default();
+        for key in [&classic, &custom, &small, &no_undo] {
+            leaderboard.insert(key.clone(), entry(100));
+        }
+
+        assert_eq!(leaderboard.tables.len(), 4);
+        assert_eq!(classic.label(), "4x4");
+        assert_eq!(custom.label(), "4x4 custom");
+        assert_eq!(no_undo.label(), "4x4 (no undo)");
+    }
+
+    #[test]
+    fn formats_dates() {
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(951_782_400), "2000-02-29");
+        assert_eq!(date(1_700_000_000), "2023-11-14");
+    }
+}
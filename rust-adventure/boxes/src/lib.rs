@@ -0,0 +1,24 @@
+//! The boxes puzzle. `game` is the plain game core, shared with the terminal
+//! front-end; `BoxesPlugin` runs it in a Bevy app, so other tools and tests
+//! can embed the puzzle.
+
+pub mod accessibility;
+pub mod achievements;
+pub mod board;
+pub mod cli;
+pub mod colors;
+pub mod events;
+pub mod export;
+pub mod game;
+pub mod inspector;
+pub mod leaderboard;
+pub mod notation;
+mod plugin;
+pub mod sfx;
+pub mod stats;
+mod storage;
+pub mod theme;
+
+pub use plugin::{
+    AppState, BoxesConfig, BoxesExtrasPlugins, BoxesPlugin, CurrentGame, InputMode, Points,
+};
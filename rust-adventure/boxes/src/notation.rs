@@ -0,0 +1,234 @@
+//! A plain-text notation for board positions, small enough to paste into a
+//! bug report:
+//!
+//! ```text
+//! boxes size 4 score 1204 seed 9876 rng 212
+//! 2 . . 4
+//! . 8 . .
+//! . . 16 .
+//! 2 . . .
+//! ```
+//!
+//! Rows go from the top of the board down, with `.` for empty cells. `rng` is
+//! how far the RNG had got into the seed's stream, so the game resumes with
+//! the same spawns; without it the RNG starts over from the seed.
+
+use crate::game::{Game, Grid, Rules};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoardState {
+    pub grid: Grid,
+    pub score: u32,
+    pub seed: u64,
+    pub rng_position: u128,
+}
+
+impl BoardState {
+    pub fn of(game: &Game) -> Self {
+        BoardState {
+            grid: game.grid.clone(),
+            score: game.score,
+            seed: game.seed(),
+            rng_position: game.rng_position(),
+        }
+    }
+
+    /// A game starting from this position.
+    pub fn into_game(self, rules: Rules) -> Game {
+        Game::resume(self.grid, self.score, self.seed, self.rng_position, rules)
+    }
+}
+
+impl fmt::Display for BoardState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let size = self.grid.size();
+        writeln!(
+            f,
+            "boxes size {} score {} seed {} rng {}",
+            size, self.score, self.seed, self.rng_position
+        )?;
+
+        for y in (0..size).rev() {
+            let row: Vec<String> = (0..size)
+                .map(|x| {
+                    self.grid
+                        .get((x, y))
+                        .map_or(".".to_string(), |value| value.to_string())
+                })
+                .collect();
+            writeln!(f, "{}", row.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based line of the notation the error is on.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn error(line: usize, message: impl Into<String>) -> ParseError {
+    ParseError {
+        line,
+        message: message.into(),
+    }
+}
+
+impl std::str::FromStr for BoardState {
+    type Err = ParseError;
+
+    /// Blank lines and surrounding whitespace are ignored, so a position
+    /// survives being quoted or indented in a bug report.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        let (header_line, header) = lines.next().ok_or_else(|| error(1, "empty position"))?;
+        let mut words = header.split_whitespace();
+        if words.next() != Some("boxes") {
+            return Err(error(header_line, "expected a line starting with 'boxes'"));
+        }
+
+        let (mut size, mut score, mut seed, mut rng_position) = (None, 0, 0, 0);
+        while let Some(key) = words.next() {
+            let value = words
+                .next()
+                .ok_or_else(|| error(header_line, format!("'{key}' has no value")))?;
+            let invalid = |_| error(header_line, format!("invalid {key} '{value}'"));
+            match key {
+                "size" => size = Some(value.parse::<u8>().map_err(invalid)?),
+                "score" => score = value.parse().map_err(invalid)?,
+                "seed" => seed = value.parse().map_err(invalid)?,
+                "rng" => rng_position = value.parse().map_err(invalid)?,
+                _ => return Err(error(header_line, format!("unknown field '{key}'"))),
+            }
+        }
+
+        let rows: Vec<(usize, Vec<&str>)> = lines
+            .map(|(line, row)| (line, row.split_whitespace().collect()))
+            .collect();
+        let size = size.unwrap_or(rows.len() as u8);
+        if size < 2 || rows.len() != usize::from(size) {
+            return Err(error(
+                header_line,
+                format!("expected {} rows, found {}", size, rows.len()),
+            ));
+        }
+
+        let mut grid = Grid::new(size);
+        for (row, (line, values)) in rows.iter().enumerate() {
+            if values.len() != usize::from(size) {
+                return Err(error(
+                    *line,
+                    format!("expected {} cells, found {}", size, values.len()),
+                ));
+            }
+
+            let y = size - 1 - row as u8;
+            for (x, value) in values.iter().enumerate() {
+                let tile = match *value {
+                    "." => None,
+                    value => match value.parse::<u32>() {
+                        Ok(tile) if tile >= 2 && tile.is_power_of_two() => Some(tile),
+                        _ => return Err(error(*line, format!("'{value}' isn't a tile"))),
+                    },
+                };
+                grid.set((x as u8, y), tile);
+            }
+        }
+
+        Ok(BoardState {
+            grid,
+            score,
+            seed,
+            rng_position,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let mut game = Game::new(4, 9876);
+        for direction in crate::game::Direction::ALL {
+            game.play(direction);
+        }
+
+        let position = BoardState::of(&game);
+        let parsed: BoardState = position.to_string().parse().unwrap();
+        assert_eq!(parsed, position);
+    }
+
+    #[test]
+    fn resumed_games_spawn_like_the_original() {
+        let mut game = Game::new(4, 9876);
+        game.play(crate::game::Direction::Left);
+
+        let mut resumed = BoardState::of(&game).into_game(Rules::default());
+        for direction in crate::game::Direction::ALL {
+            let expected = game.play(direction).map(|turn| turn.spawned);
+            assert_eq!(resumed.play(direction).map(|turn| turn.spawned), expected);
+        }
+    }
+
+    #[test]
+    fn reads_pasted_positions() {
+        let position: BoardState = "
+            boxes size 3 score 20 seed 7
+
+            2 . 4
+            . 8 .
+            . . 2
+        "
+        .parse()
+        .unwrap();
+
+        assert_eq!(position.score, 20);
+        assert_eq!(position.seed, 7);
+        assert_eq!(position.grid.get((0, 2)), Some(2));
+        assert_eq!(position.grid.get((2, 2)), Some(4));
+        assert_eq!(position.grid.get((1, 1)), Some(8));
+        assert_eq!(position.grid.get((2, 0)), Some(2));
+        assert_eq!(position.grid.tiles().count(), 4);
+    }
+
+    #[test]
+    fn points_at_bad_lines() {
+        let bad_tile = "boxes size 2\n2 .\n3 .".parse::<BoardState>();
+        assert_eq!(bad_tile.unwrap_err().line, 3);
+
+        let short_row = "boxes size 2\n2 .\n2".parse::<BoardState>();
+        assert_eq!(short_row.unwrap_err().line, 3);
+
+        let wrong_size = "boxes size 3\n2 .\n. .".parse::<BoardState>();
+        assert_eq!(wrong_size.unwrap_err().line, 1);
+
+        assert!("2 .\n. .".parse::<BoardState>().is_err());
+    }
+
+    #[test]
+    fn resumes_finished_games_as_over() {
+        let position: BoardState = "boxes\n2 4\n4 2".parse().unwrap();
+        let game = position.into_game(Rules::default());
+
+        assert!(game.over);
+        assert!(!game.won);
+    }
+}
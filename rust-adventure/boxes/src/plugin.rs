@@ -0,0 +1,672 @@
+use crate::accessibility::AccessibilityPlugin;
+use crate::achievements::AchievementsPlugin;
+use crate::board::{Board, Position, TilePlaceholder};
+use crate::colors;
+use crate::events::{
+    BoardEdited, GameEnded, GameStarted, HintUsed, MoveBlocked, MoveMade, PlayerAction, TileMerged,
+    TileMoved, TileRemoved, TileSpawned, UndoUsed,
+};
+use crate::export::ExportPlugin;
+use crate::game::{Direction, Game, Grid, Rules};
+use crate::inspector::InspectorPlugin;
+use crate::leaderboard::LeaderboardPlugin;
+use crate::sfx::SfxPlugin;
+use crate::stats::StatsPlugin;
+use crate::theme::{ActiveTheme, ThemePlugin};
+use bevy::{
+    app::PluginGroupBuilder,
+    ecs::system::SystemParam,
+    prelude::*,
+    utils::HashMap,
+    window::{PrimaryWindow, WindowResized},
+};
+use itertools::Itertools;
+
+/// Board layout used when there's no window to fit, e.g. under `MinimalPlugins`.
+const HEADLESS_WINDOW_SIZE: Vec2 = Vec2::new(640.0, 480.0);
+
+/// The puzzle itself: game state, board, tiles, HUD and input. It only needs
+/// `MinimalPlugins` to run; the themes, stats, achievements and sounds live in
+/// `BoxesExtrasPlugins`.
+pub struct BoxesPlugin {
+    pub config: BoxesConfig,
+}
+
+#[derive(Resource, Clone)]
+pub struct BoxesConfig {
+    pub size: u8,
+    pub seed: u64,
+    pub rules: Rules,
+    /// A position to start from instead of a freshly dealt board, e.g. one
+    /// loaded from `notation`. `size`, `seed` and `rules` are ignored then.
+    pub start: Option<Game>,
+    /// Theme asset to start with, instead of the first built-in one.
+    pub theme: Option<String>,
+    pub input: InputMode,
+}
+
+impl Default for BoxesConfig {
+    fn default() -> Self {
+        BoxesConfig {
+            size: 4,
+            seed: rand::random(),
+            rules: Rules::default(),
+            start: None,
+            theme: None,
+            input: InputMode::Keyboard,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputMode {
+    /// Arrows slide, backspace undoes, `H` hints, `R` restarts, `Tab`, `A`
+    /// and `L` open the stats, achievements and leaderboard screens.
+    Keyboard,
+    /// Nothing reads the keyboard; whoever embeds the puzzle sends
+    /// `PlayerAction` events instead.
+    Events,
+}
+
+impl Plugin for BoxesPlugin {
+    fn build(&self, app: &mut App) {
+        let config = self.config.clone();
+
+        let game = config
+            .start
+            .clone()
+            .unwrap_or_else(|| Game::with_rules(config.size, config.seed, config.rules));
+
+        app.insert_resource(CurrentGame(game))
+            .insert_resource(config.clone())
+            .init_resource::<ActiveTheme>()
+            // Normally registered by `WindowPlugin`, which headless apps don't have.
+            .add_event::<WindowResized>()
+            .add_state::<AppState>()
+            .add_event::<PlayerAction>()
+            .add_event::<GameStarted>()
+            .add_event::<BoardEdited>()
+            .add_event::<MoveMade>()
+            .add_event::<MoveBlocked>()
+            .add_event::<GameEnded>()
+            .add_event::<UndoUsed>()
+            .add_event::<HintUsed>()
+            .add_event::<TileSpawned>()
+            .add_event::<TileMoved>()
+            .add_event::<TileMerged>()
+            .add_event::<TileRemoved>()
+            .add_systems(
+                Startup,
+                (setup, spawn_board, apply_deferred, spawn_tiles).chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    (move_tiles, undo_move, show_hint, restart_game)
+                        .run_if(in_state(AppState::Playing)),
+                    rebuild_tiles,
+                    layout_board,
+                    despawn_removed_tiles
+                        .after(move_tiles)
+                        .after(undo_move)
+                        .after(restart_game)
+                        .after(rebuild_tiles),
+                    render_tile_positions,
+                    render_tile_points.after(layout_board),
+                    render_hud,
+                ),
+            );
+
+        if config.input == InputMode::Keyboard {
+            app.add_systems(
+                Update,
+                (
+                    toggle_screens,
+                    read_keyboard
+                        .run_if(in_state(AppState::Playing))
+                        .before(move_tiles)
+                        .before(undo_move)
+                        .before(show_hint)
+                        .before(restart_game),
+                ),
+            );
+        }
+    }
+}
+
+/// Everything around the puzzle that needs assets, a window or the disk:
+/// themes, colour-blind palettes, stats, achievements, the leaderboard, sound
+/// effects and board exports, plus the inspector in debug builds. Add it
+/// after `BoxesPlugin` and `DefaultPlugins`.
+pub struct BoxesExtrasPlugins;
+
+impl PluginGroup for BoxesExtrasPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        let group = PluginGroupBuilder::start::<Self>()
+            .add(ThemePlugin)
+            .add(AccessibilityPlugin)
+            .add(StatsPlugin)
+            .add(AchievementsPlugin)
+            .add(LeaderboardPlugin)
+            .add(SfxPlugin)
+            .add(ExportPlugin);
+
+        if cfg!(debug_assertions) {
+            group.add(InspectorPlugin)
+        } else {
+            group
+        }
+    }
+}
+
+#[derive(States, Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum AppState {
+    #[default]
+    Playing,
+    Stats,
+    Achievements,
+    Leaderboard,
+    /// Typing a name for a new leaderboard entry.
+    NameEntry,
+}
+
+#[derive(Component)]
+pub struct Points {
+    pub value: u32,
+}
+
+#[derive(Component)]
+struct TileText;
+
+/// The text entity showing a tile's points.
+#[derive(Component)]
+struct TileLabel(Entity);
+
+#[derive(SystemParam)]
+struct TileEvents<'w> {
+    spawned: EventWriter<'w, TileSpawned>,
+    moved: EventWriter<'w, TileMoved>,
+    merged: EventWriter<'w, TileMerged>,
+    removed: EventWriter<'w, TileRemoved>,
+}
+
+/// Writers for what a turn or restart tells the rest of the game.
+#[derive(SystemParam)]
+struct TurnEvents<'w> {
+    moves: EventWriter<'w, MoveMade>,
+    blocked: EventWriter<'w, MoveBlocked>,
+    ended: EventWriter<'w, GameEnded>,
+    started: EventWriter<'w, GameStarted>,
+}
+
+#[derive(Component)]
+struct Hud;
+
+/// The game being played, wrapping the shared `boxes` game core.
+#[derive(Resource, Deref, DerefMut)]
+pub struct CurrentGame(pub Game);
+
+impl CurrentGame {
+    fn ended(&self, won: bool) -> GameEnded {
+        GameEnded {
+            won,
+            abandoned: false,
+            score: self.score,
+            largest_tile: self.grid.max_tile(),
+            moves: self.moves,
+            seed: self.seed(),
+            mode: self.mode(),
+        }
+    }
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+
+    let text_style = TextStyle {
+        font_size: 20.0,
+        color: Color::WHITE,
+        ..default()
+    };
+    commands
+        .spawn(
+            TextBundle::from_sections([
+                TextSection::new("", text_style.clone()),
+                TextSection::new("", text_style),
+            ])
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(5.0),
+                left: Val::Px(5.0),
+                ..default()
+            }),
+        )
+        .insert(Hud);
+}
+
+fn spawn_board(
+    mut commands: Commands,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    game: Res<CurrentGame>,
+) {
+    let window_size = windows.get_single().map_or(HEADLESS_WINDOW_SIZE, |window| {
+        Vec2::new(window.width(), window.height())
+    });
+    let board = Board::new(game.grid.size(), window_size.x, window_size.y);
+
+    commands
+        .spawn(board.make_board_sprite())
+        .with_children(|builder| {
+            for tile in (0..board.size).cartesian_product(0..board.size) {
+                let pos = Position {
+                    x: tile.0,
+                    y: tile.1,
+                };
+                builder
+                    .spawn(board.make_tile_sprite(&pos, colors::TILE_PLACEHOLDER))
+                    .insert(TilePlaceholder)
+                    .insert(pos);
+            }
+        })
+        .insert(board);
+}
+
+fn spawn_tiles(
+    mut commands: Commands,
+    query_board: Query<&Board>,
+    game: Res<CurrentGame>,
+    mut tile_events: TileEvents,
+) {
+    let board = query_board.single();
+
+    for (cell, value) in game.grid.tiles() {
+        spawn_tile(&mut commands, &mut tile_events, board, cell, value);
+    }
+}
+
+fn spawn_tile(
+    commands: &mut Commands,
+    tile_events: &mut TileEvents,
+    board: &Board,
+    cell: (u8, u8),
+    value: u32,
+) {
+    let pos = Position {
+        x: cell.0,
+        y: cell.1,
+    };
+    let label = commands
+        .spawn(Text2dBundle {
+            text: Text::from_section(
+                value.to_string(),
+                TextStyle {
+                    font_size: board.text_size(),
+                    color: Color::BLACK,
+                    ..default()
+                },
+            )
+            .with_alignment(TextAlignment::Center),
+            transform: Transform::from_xyz(0.0, 0.0, 1.0),
+            ..default()
+        })
+        .insert(TileText)
+        .id();
+
+    let entity = commands
+        .spawn(board.make_tile_sprite(&pos, colors::TILE))
+        .add_child(label)
+        .insert(TileLabel(label))
+        .insert(Points { value })
+        .insert(pos)
+        .id();
+
+    tile_events.spawned.send(TileSpawned {
+        entity,
+        cell,
+        value,
+    });
+}
+
+fn respawn_tiles(
+    commands: &mut Commands,
+    tile_events: &mut TileEvents,
+    board: &Board,
+    grid: &Grid,
+    tiles: impl Iterator<Item = Entity>,
+) {
+    for entity in tiles {
+        tile_events.removed.send(TileRemoved { entity });
+    }
+    for (cell, value) in grid.tiles() {
+        spawn_tile(commands, tile_events, board, cell, value);
+    }
+}
+
+fn toggle_screens(
+    keys: Res<Input<KeyCode>>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let screen = if keys.just_pressed(KeyCode::Tab) {
+        AppState::Stats
+    } else if keys.just_pressed(KeyCode::A) {
+        AppState::Achievements
+    } else if keys.just_pressed(KeyCode::L) {
+        AppState::Leaderboard
+    } else {
+        return;
+    };
+
+    if *state.get() == screen {
+        next_state.set(AppState::Playing);
+    } else {
+        next_state.set(screen);
+    }
+}
+
+fn read_keyboard(keys: Res<Input<KeyCode>>, mut actions: EventWriter<PlayerAction>) {
+    let keymap = [
+        (KeyCode::Up, PlayerAction::Slide(Direction::Up)),
+        (KeyCode::Down, PlayerAction::Slide(Direction::Down)),
+        (KeyCode::Left, PlayerAction::Slide(Direction::Left)),
+        (KeyCode::Right, PlayerAction::Slide(Direction::Right)),
+        (KeyCode::Back, PlayerAction::Undo),
+        (KeyCode::H, PlayerAction::Hint),
+        (KeyCode::R, PlayerAction::Restart),
+    ];
+
+    for (key, action) in keymap {
+        if keys.just_pressed(key) {
+            actions.send(action);
+        }
+    }
+}
+
+fn move_tiles(
+    mut commands: Commands,
+    mut actions: EventReader<PlayerAction>,
+    mut game: ResMut<CurrentGame>,
+    boards: Query<&Board>,
+    mut tiles: Query<(Entity, &mut Position, &mut Points)>,
+    mut tile_events: TileEvents,
+    mut turn_events: TurnEvents,
+) {
+    // One slide per frame, so the tile entities stay in sync with the grid;
+    // the reader stops there and picks up any further slides next frame.
+    let Some(direction) = actions.iter().find_map(|action| match action {
+        PlayerAction::Slide(direction) => Some(*direction),
+        _ => None,
+    }) else {
+        return;
+    };
+
+    if game.over {
+        return;
+    }
+    let Some(turn) = game.play(direction) else {
+        turn_events.blocked.send(MoveBlocked { direction });
+        return;
+    };
+    let outcome = &turn.outcome;
+
+    let board = boards.single();
+    let by_cell: HashMap<(u8, u8), Entity> = tiles
+        .iter()
+        .map(|(entity, pos, _)| ((pos.x, pos.y), entity))
+        .collect();
+
+    for slide in outcome.slides.iter() {
+        let entity = by_cell[&slide.from];
+        if let Ok((_, mut pos, _)) = tiles.get_mut(entity) {
+            pos.x = slide.to.0;
+            pos.y = slide.to.1;
+        }
+        tile_events.moved.send(TileMoved {
+            entity,
+            from: slide.from,
+            to: slide.to,
+        });
+    }
+
+    for merge in outcome.merges.iter() {
+        // The surviving tile either slid into the merge cell or was already there.
+        let survivor_from = outcome
+            .slides
+            .iter()
+            .find(|slide| slide.to == merge.into)
+            .map_or(merge.into, |slide| slide.from);
+        let entity = by_cell[&survivor_from];
+        let absorbed = by_cell[&merge.from];
+
+        if let Ok((_, _, mut points)) = tiles.get_mut(entity) {
+            points.value = merge.value;
+        }
+        tile_events.merged.send(TileMerged {
+            entity,
+            absorbed,
+            cell: merge.into,
+            value: merge.value,
+        });
+        tile_events.removed.send(TileRemoved { entity: absorbed });
+    }
+
+    if let Some((cell, value)) = turn.spawned {
+        spawn_tile(&mut commands, &mut tile_events, board, cell, value);
+    }
+
+    turn_events.moves.send(MoveMade {
+        direction,
+        merges: outcome.merges.len() as u32,
+        score_gained: outcome.score,
+    });
+
+    if let Some(won) = turn.ended {
+        turn_events.ended.send(game.ended(won));
+    }
+}
+
+fn undo_move(
+    mut commands: Commands,
+    mut actions: EventReader<PlayerAction>,
+    mut game: ResMut<CurrentGame>,
+    boards: Query<&Board>,
+    tiles: Query<Entity, With<Points>>,
+    mut tile_events: TileEvents,
+    mut undos: EventWriter<UndoUsed>,
+) {
+    let requested = actions.iter().any(|action| *action == PlayerAction::Undo);
+    if !requested || !game.undo() {
+        return;
+    }
+
+    respawn_tiles(
+        &mut commands,
+        &mut tile_events,
+        boards.single(),
+        &game.grid,
+        tiles.iter(),
+    );
+    undos.send(UndoUsed);
+}
+
+fn show_hint(
+    mut actions: EventReader<PlayerAction>,
+    mut game: ResMut<CurrentGame>,
+    mut hints: EventWriter<HintUsed>,
+) {
+    let requested = actions.iter().any(|action| *action == PlayerAction::Hint);
+    if !requested || game.over {
+        return;
+    }
+
+    game.show_hint();
+    hints.send(HintUsed);
+}
+
+fn restart_game(
+    mut commands: Commands,
+    mut actions: EventReader<PlayerAction>,
+    mut game: ResMut<CurrentGame>,
+    boards: Query<&Board>,
+    tiles: Query<Entity, With<Points>>,
+    mut tile_events: TileEvents,
+    mut turn_events: TurnEvents,
+) {
+    if !actions
+        .iter()
+        .any(|action| *action == PlayerAction::Restart)
+    {
+        return;
+    }
+
+    // Abandoning a game that was actually played still counts as a loss.
+    if !game.over && game.moves > 0 {
+        turn_events.ended.send(GameEnded {
+            abandoned: true,
+            ..game.ended(false)
+        });
+    }
+
+    game.restart();
+    respawn_tiles(
+        &mut commands,
+        &mut tile_events,
+        boards.single(),
+        &game.grid,
+        tiles.iter(),
+    );
+    turn_events.started.send(GameStarted);
+}
+
+fn rebuild_tiles(
+    mut commands: Commands,
+    mut edits: EventReader<BoardEdited>,
+    game: Res<CurrentGame>,
+    boards: Query<&Board>,
+    tiles: Query<Entity, With<Points>>,
+    mut tile_events: TileEvents,
+    mut ended: EventWriter<GameEnded>,
+) {
+    if edits.iter().count() == 0 {
+        return;
+    }
+
+    respawn_tiles(
+        &mut commands,
+        &mut tile_events,
+        boards.single(),
+        &game.grid,
+        tiles.iter(),
+    );
+
+    // Edits are refused once the game is over, so the last one ended it.
+    if game.over {
+        ended.send(game.ended(false));
+    }
+}
+
+/// Lays out and colours the whole board when the window or theme changes.
+fn layout_board(
+    mut resize_events: EventReader<WindowResized>,
+    active: Res<ActiveTheme>,
+    mut boards: Query<(&mut Board, &mut Sprite)>,
+    mut tiles: Query<
+        (
+            &Position,
+            Option<&Points>,
+            &mut Transform,
+            &mut Sprite,
+            &mut Handle<Image>,
+        ),
+        Without<Board>,
+    >,
+    mut texts: Query<&mut Text, With<TileText>>,
+) {
+    let resized = resize_events.iter().last();
+    if resized.is_none() && !active.is_changed() {
+        return;
+    }
+
+    let theme = &active.theme;
+    let (mut board, mut board_sprite) = boards.single_mut();
+    if let Some(resized) = resized {
+        board.resize(resized.width, resized.height);
+    }
+    board.set_spacing(theme.spacing);
+    board_sprite.custom_size = Some(board.board_sprite_size());
+    board_sprite.color = theme.board;
+
+    for (pos, points, mut transform, mut sprite, mut texture) in tiles.iter_mut() {
+        transform.translation = board.tile_translation(pos, transform.translation.z);
+        sprite.custom_size = Some(board.tile_sprite_size());
+        sprite.color = match points {
+            Some(points) => theme.tile_color(points.value),
+            None => theme.placeholder,
+        };
+        *texture = active.corners.clone();
+    }
+
+    for mut text in texts.iter_mut() {
+        for section in text.sections.iter_mut() {
+            section.style.font_size = board.text_size();
+            section.style.font = active.font.clone();
+            section.style.color = theme.text;
+        }
+    }
+}
+
+fn despawn_removed_tiles(mut commands: Commands, mut removed: EventReader<TileRemoved>) {
+    for event in removed.iter() {
+        if let Some(entity) = commands.get_entity(event.entity) {
+            entity.despawn_recursive();
+        }
+    }
+}
+
+fn render_tile_positions(
+    boards: Query<&Board>,
+    mut tiles: Query<(&Position, &mut Transform), (With<Points>, Changed<Position>)>,
+) {
+    let board = boards.single();
+    for (pos, mut transform) in tiles.iter_mut() {
+        transform.translation = board.tile_translation(pos, transform.translation.z);
+    }
+}
+
+fn render_tile_points(
+    active: Res<ActiveTheme>,
+    mut texts: Query<&mut Text, With<TileText>>,
+    mut tiles: Query<(&Points, &TileLabel, &mut Sprite, &mut Handle<Image>), Changed<Points>>,
+) {
+    let theme = &active.theme;
+    for (points, label, mut sprite, mut texture) in tiles.iter_mut() {
+        sprite.color = theme.tile_color(points.value);
+        *texture = active.corners.clone();
+
+        if let Ok(mut text) = texts.get_mut(label.0) {
+            if let Some(section) = text.sections.first_mut() {
+                section.value = points.value.to_string();
+                section.style.font = active.font.clone();
+                section.style.color = theme.text;
+            }
+        }
+    }
+}
+
+fn render_hud(game: Res<CurrentGame>, mut huds: Query<&mut Text, With<Hud>>) {
+    if !game.is_changed() {
+        return;
+    }
+
+    let mut text = huds.single_mut();
+    text.sections[0].value = format!("score {}\n", game.score);
+    text.sections[1].value = if game.won {
+        "you win! (R)".to_string()
+    } else if game.over {
+        "game over (R)".to_string()
+    } else if let Some(hint) = game.hint {
+        format!("hint: {}", hint.name())
+    } else {
+        String::new()
+    };
+}
@@ -0,0 +1,151 @@
+use crate::events::{GameEnded, MoveBlocked, MoveMade, TileMerged, TileSpawned};
+use crate::storage;
+use bevy::{audio::Volume, ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+const SETTINGS_FILE: &str = "audio.ron";
+const VOLUME_STEP: f32 = 0.1;
+
+/// Plays sound effects in response to gameplay events. Leave it out of the
+/// app (e.g. in headless tests) and the game stays silent.
+pub struct SfxPlugin;
+
+impl Plugin for SfxPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(storage::load::<SfxSettings>(SETTINGS_FILE))
+            .add_systems(Startup, load_sounds)
+            .add_systems(Update, (adjust_volume, play_sounds));
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SfxSettings {
+    pub master_volume: f32,
+    pub sfx_volume: f32,
+    pub muted: bool,
+}
+
+impl Default for SfxSettings {
+    fn default() -> Self {
+        SfxSettings {
+            master_volume: 1.0,
+            sfx_volume: 0.8,
+            muted: false,
+        }
+    }
+}
+
+impl SfxSettings {
+    fn volume(&self) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            self.master_volume * self.sfx_volume
+        }
+    }
+}
+
+#[derive(Resource)]
+struct Sounds {
+    slide: Handle<AudioSource>,
+    merge: Handle<AudioSource>,
+    spawn: Handle<AudioSource>,
+    blocked: Handle<AudioSource>,
+    win: Handle<AudioSource>,
+    game_over: Handle<AudioSource>,
+}
+
+fn load_sounds(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Sounds {
+        slide: asset_server.load("sounds/slide.wav"),
+        merge: asset_server.load("sounds/merge.wav"),
+        spawn: asset_server.load("sounds/spawn.wav"),
+        blocked: asset_server.load("sounds/blocked.wav"),
+        win: asset_server.load("sounds/win.wav"),
+        game_over: asset_server.load("sounds/game_over.wav"),
+    });
+}
+
+/// `M` mutes, `-`/`=` change the master volume and `[`/`]` the effects volume.
+fn adjust_volume(keys: Res<Input<KeyCode>>, mut settings: ResMut<SfxSettings>) {
+    let step = |volume: f32, up: KeyCode, down: KeyCode| {
+        if keys.just_pressed(up) {
+            (volume + VOLUME_STEP).min(1.0)
+        } else if keys.just_pressed(down) {
+            (volume - VOLUME_STEP).max(0.0)
+        } else {
+            volume
+        }
+    };
+
+    let master_volume = step(settings.master_volume, KeyCode::Equals, KeyCode::Minus);
+    let sfx_volume = step(
+        settings.sfx_volume,
+        KeyCode::BracketRight,
+        KeyCode::BracketLeft,
+    );
+    let muted = settings.muted ^ keys.just_pressed(KeyCode::M);
+
+    if master_volume != settings.master_volume
+        || sfx_volume != settings.sfx_volume
+        || muted != settings.muted
+    {
+        settings.master_volume = master_volume;
+        settings.sfx_volume = sfx_volume;
+        settings.muted = muted;
+        storage::save(SETTINGS_FILE, &*settings);
+    }
+}
+
+/// Readers for every event that makes a sound.
+#[derive(SystemParam)]
+struct SoundEvents<'w, 's> {
+    moves: EventReader<'w, 's, MoveMade>,
+    merges: EventReader<'w, 's, TileMerged>,
+    spawns: EventReader<'w, 's, TileSpawned>,
+    blocked: EventReader<'w, 's, MoveBlocked>,
+    ended: EventReader<'w, 's, GameEnded>,
+}
+
+fn play_sounds(
+    mut commands: Commands,
+    sounds: Res<Sounds>,
+    settings: Res<SfxSettings>,
+    mut events: SoundEvents,
+) {
+    let volume = settings.volume();
+    let mut play = |source: &Handle<AudioSource>, speed: f32| {
+        if volume > 0.0 {
+            commands.spawn(AudioBundle {
+                source: source.clone(),
+                settings: PlaybackSettings::DESPAWN
+                    .with_volume(Volume::new_relative(volume))
+                    .with_speed(speed),
+            });
+        }
+    };
+
+    // One sound per kind per frame, however many tiles were involved.
+    if events.moves.iter().count() > 0 {
+        play(&sounds.slide, 1.0);
+    }
+    if let Some(largest) = events.merges.iter().map(|merged| merged.value).max() {
+        // Each doubling raises the pitch a semitone.
+        let semitones = largest.max(2).ilog2() as f32 - 1.0;
+        play(&sounds.merge, 2f32.powf(semitones / 12.0));
+    }
+    if events.spawns.iter().count() > 0 {
+        play(&sounds.spawn, 1.0);
+    }
+    if events.blocked.iter().count() > 0 {
+        play(&sounds.blocked, 1.0);
+    }
+    for game_ended in events.ended.iter() {
+        if game_ended.won {
+            play(&sounds.win, 1.0);
+        } else if !game_ended.abandoned {
+            play(&sounds.game_over, 1.0);
+        }
+    }
+}
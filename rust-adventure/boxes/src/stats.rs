@@ -0,0 +1,301 @@
+use crate::events::{GameEnded, GameStarted, HintUsed, MoveMade, UndoUsed};
+use crate::theme::ActiveTheme;
+use crate::{storage, AppState, CurrentGame};
+use bevy::{ecs::system::SystemParam, prelude::*, window::PrimaryWindow};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const STATS_FILE: &str = "stats.ron";
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(storage::load::<LifetimeStats>(STATS_FILE))
+            .init_resource::<GameStats>()
+            .add_systems(
+                Update,
+                (
+                    track_game_stats,
+                    tick_time_played.run_if(in_state(AppState::Playing)),
+                ),
+            )
+            .add_systems(OnEnter(AppState::Stats), spawn_stats_screen)
+            .add_systems(OnExit(AppState::Stats), despawn_stats_screen);
+    }
+}
+
+/// Stats for the game currently being played.
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+pub struct GameStats {
+    pub score: u32,
+    pub moves: u32,
+    pub merges: u32,
+    pub largest_tile: u32,
+    pub time_played: f32,
+    pub undos: u32,
+    pub hints: u32,
+}
+
+#[derive(Resource, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LifetimeStats {
+    pub games_played: u32,
+    pub games_won: u32,
+    pub total_score: u64,
+    pub total_moves: u64,
+    pub total_merges: u64,
+    pub total_time_played: f64,
+    pub total_undos: u32,
+    pub total_hints: u32,
+    /// How many games ended with each value as their highest tile.
+    pub highest_tiles: BTreeMap<u32, u32>,
+    pub last_game: Option<GameStats>,
+}
+
+impl LifetimeStats {
+    pub fn win_rate(&self) -> f32 {
+        if self.games_played == 0 {
+            return 0.0;
+        }
+        self.games_won as f32 / self.games_played as f32
+    }
+
+    pub fn average_score(&self) -> f32 {
+        if self.games_played == 0 {
+            return 0.0;
+        }
+        self.total_score as f32 / self.games_played as f32
+    }
+
+    fn record(&mut self, game: &GameStats, won: bool) {
+        self.games_played += 1;
+        if won {
+            self.games_won += 1;
+        }
+        self.total_score += u64::from(game.score);
+        self.total_moves += u64::from(game.moves);
+        self.total_merges += u64::from(game.merges);
+        self.total_time_played += f64::from(game.time_played);
+        self.total_undos += game.undos;
+        self.total_hints += game.hints;
+        *self.highest_tiles.entry(game.largest_tile).or_default() += 1;
+        self.last_game = Some(game.clone());
+    }
+}
+
+#[derive(Component)]
+struct StatsScreen;
+
+/// Readers for everything the player did that the stats count.
+#[derive(SystemParam)]
+pub(crate) struct PlayEvents<'w, 's> {
+    moves: EventReader<'w, 's, MoveMade>,
+    undos: EventReader<'w, 's, UndoUsed>,
+    hints: EventReader<'w, 's, HintUsed>,
+    ended: EventReader<'w, 's, GameEnded>,
+    started: EventReader<'w, 's, GameStarted>,
+}
+
+pub(crate) fn track_game_stats(
+    mut stats: ResMut<GameStats>,
+    mut lifetime: ResMut<LifetimeStats>,
+    game: Res<CurrentGame>,
+    mut events: PlayEvents,
+) {
+    for made in events.moves.iter() {
+        stats.moves += 1;
+        stats.merges += made.merges;
+    }
+    stats.undos += events.undos.iter().count() as u32;
+    stats.hints += events.hints.iter().count() as u32;
+
+    // The game may already have been restarted by now, so the final numbers
+    // come from the event rather than from `CurrentGame`.
+    for game_ended in events.ended.iter() {
+        stats.score = game_ended.score;
+        stats.largest_tile = stats.largest_tile.max(game_ended.largest_tile);
+        lifetime.record(&stats, game_ended.won);
+        storage::save(STATS_FILE, &*lifetime);
+    }
+
+    if events.started.iter().count() > 0 {
+        *stats = GameStats::default();
+    }
+
+    if game.is_changed() {
+        stats.score = game.score;
+        stats.largest_tile = stats.largest_tile.max(game.grid.max_tile());
+    }
+}
+
+fn tick_time_played(mut stats: ResMut<GameStats>, game: Res<CurrentGame>, time: Res<Time>) {
+    if !game.over {
+        stats.time_played += time.delta_seconds();
+    }
+}
+
+fn spawn_stats_screen(
+    mut commands: Commands,
+    lifetime: Res<LifetimeStats>,
+    active: Res<ActiveTheme>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+) {
+    let window = windows.single();
+    let (width, height) = (window.width(), window.height());
+    let text_style = TextStyle {
+        font_size: (height / 16.0).max(10.0),
+        color: Color::WHITE,
+        ..default()
+    };
+
+    let summary = format!(
+        "games {}   won {:.0}%   avg score {:.0}\nmoves {}   merges {}   time {:.0}m   undos {}   hints {}",
+        lifetime.games_played,
+        lifetime.win_rate() * 100.0,
+        lifetime.average_score(),
+        lifetime.total_moves,
+        lifetime.total_merges,
+        lifetime.total_time_played / 60.0,
+        lifetime.total_undos,
+        lifetime.total_hints,
+    );
+    let last_game = match &lifetime.last_game {
+        Some(game) => format!(
+            "last game: score {}   best tile {}   moves {}   merges {}   time {:.0}s   undos {}   hints {}",
+            game.score,
+            game.largest_tile,
+            game.moves,
+            game.merges,
+            game.time_played,
+            game.undos,
+            game.hints,
+        ),
+        None => "no games finished yet".to_string(),
+    };
+
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                color: active.theme.board,
+                custom_size: Some(Vec2::new(width, height)),
+                ..default()
+            },
+            transform: Transform::from_xyz(0.0, 0.0, 10.0),
+            ..default()
+        })
+        .insert(StatsScreen)
+        .with_children(|builder| {
+            builder.spawn(Text2dBundle {
+                text: Text::from_section(summary, text_style.clone())
+                    .with_alignment(TextAlignment::Center),
+                transform: Transform::from_xyz(0.0, height * 0.36, 1.0),
+                ..default()
+            });
+            builder.spawn(Text2dBundle {
+                text: Text::from_section(
+                    last_game,
+                    TextStyle {
+                        font_size: text_style.font_size * 0.75,
+                        ..text_style.clone()
+                    },
+                )
+                .with_alignment(TextAlignment::Center),
+                transform: Transform::from_xyz(0.0, height * 0.24, 1.0),
+                ..default()
+            });
+
+            // Distribution of the highest tile reached per game.
+            let bar_count = lifetime.highest_tiles.len().max(1) as f32;
+            let slot_width = width * 0.8 / bar_count;
+            let chart_height = height * 0.5;
+            let chart_bottom = -height * 0.35;
+            let most_games = lifetime.highest_tiles.values().max().copied().unwrap_or(1);
+
+            for (i, (tile, games)) in lifetime.highest_tiles.iter().enumerate() {
+                let x = -width * 0.4 + slot_width * (i as f32 + 0.5);
+                let bar_height = chart_height * *games as f32 / most_games as f32;
+
+                builder.spawn(SpriteBundle {
+                    sprite: Sprite {
+                        color: active.theme.tile_color(*tile),
+                        custom_size: Some(Vec2::new(slot_width * 0.7, bar_height)),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(x, chart_bottom + bar_height / 2.0, 1.0),
+                    ..default()
+                });
+                builder.spawn(Text2dBundle {
+                    text: Text::from_section(tile.to_string(), text_style.clone())
+                        .with_alignment(TextAlignment::Center),
+                    transform: Transform::from_xyz(x, chart_bottom - text_style.font_size, 1.0),
+                    ..default()
+                });
+                builder.spawn(Text2dBundle {
+                    text: Text::from_section(games.to_string(), text_style.clone())
+                        .with_alignment(TextAlignment::Center),
+                    transform: Transform::from_xyz(
+                        x,
+                        chart_bottom + bar_height + text_style.font_size / 2.0,
+                        1.0,
+                    ),
+                    ..default()
+                });
+            }
+        });
+}
+
+fn despawn_stats_screen(mut commands: Commands, screens: Query<Entity, With<StatsScreen>>) {
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(score: u32, largest_tile: u32) -> GameStats {
+        GameStats {
+            score,
+            moves: 10,
+            merges: 4,
+            largest_tile,
+            time_played: 1.5,
+            undos: 1,
+            hints: 2,
+        }
+    }
+
+    #[test]
+    fn no_games_means_no_rates() {
+        let lifetime = LifetimeStats::default();
+
+        assert_eq!(lifetime.win_rate(), 0.0);
+        assert_eq!(lifetime.average_score(), 0.0);
+    }
+
+    #[test]
+    fn recording_adds_each_game_up() {
+        let mut lifetime = LifetimeStats::default();
+        lifetime.record(&game(100, 64), false);
+        lifetime.record(&game(300, 2048), true);
+        lifetime.record(&game(200, 64), false);
+
+        assert_eq!(lifetime.games_played, 3);
+        assert_eq!(lifetime.games_won, 1);
+        assert_eq!(lifetime.total_score, 600);
+        assert_eq!(lifetime.total_moves, 30);
+        assert_eq!(lifetime.total_merges, 12);
+        assert_eq!(lifetime.total_time_played, 4.5);
+        assert_eq!(lifetime.total_undos, 3);
+        assert_eq!(lifetime.total_hints, 6);
+        assert_eq!(lifetime.highest_tiles, BTreeMap::from([(64, 2), (2048, 1)]));
+        assert_eq!(
+            lifetime.last_game.as_ref().map(|game| game.score),
+            Some(200)
+        );
+        assert!((lifetime.win_rate() - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(lifetime.average_score(), 200.0);
+    }
+}
@@ -0,0 +1,44 @@
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{env, fs, path::PathBuf};
+
+/// Where saved data lives: `$BOXES_DATA_DIR`, then `~/.local/share/boxes`,
+/// then the working directory.
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("BOXES_DATA_DIR") {
+        return PathBuf::from(dir);
+    }
+
+    match env::var_os("HOME") {
+        Some(home) => PathBuf::from(home).join(".local/share/boxes"),
+        None => PathBuf::from("."),
+    }
+}
+
+pub fn load<T: DeserializeOwned + Default>(file_name: &str) -> T {
+    let path = data_dir().join(file_name);
+    let Ok(contents) = fs::read_to_string(&path) else {
+        return T::default();
+    };
+
+    ron::from_str(&contents).unwrap_or_else(|err| {
+        warn!("ignoring unreadable {}: {}", path.display(), err);
+        T::default()
+    })
+}
+
+pub fn save<T: Serialize>(file_name: &str, value: &T) {
+    let dir = data_dir();
+    let path = dir.join(file_name);
+    let result = fs::create_dir_all(&dir)
+        .map_err(|err| err.to_string())
+        .and_then(|_| {
+            ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+                .map_err(|err| err.to_string())
+        })
+        .and_then(|contents| fs::write(&path, contents).map_err(|err| err.to_string()));
+
+    if let Err(err) = result {
+        warn!("couldn't save {}: {}", path.display(), err);
+    }
+}
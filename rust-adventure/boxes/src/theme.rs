@@ -0,0 +1,231 @@
+use crate::accessibility::AccessibilitySettings;
+use crate::colors;
+use crate::BoxesConfig;
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    ecs::system::SystemParam,
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    render::{
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::DEFAULT_IMAGE_HANDLE,
+    },
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+/// Themes the player can cycle through with `T`, in order.
+const THEME_FILES: &[&str] = &[
+    "themes/default.theme.ron",
+    "themes/daylight.theme.ron",
+    "themes/terminal.theme.ron",
+];
+const CORNER_TEXTURE_SIZE: u32 = 64;
+
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Theme>()
+            .init_asset_loader::<ThemeLoader>()
+            .add_systems(Startup, load_themes)
+            .add_systems(Update, (cycle_themes, apply_theme.after(cycle_themes)));
+    }
+}
+
+#[derive(Deserialize, TypeUuid, TypePath, Clone, Debug)]
+#[uuid = "5b1f6f8e-3f0c-4d5e-9a49-0c6f3ad1b7a2"]
+pub struct Theme {
+    pub name: String,
+    pub background: Color,
+    pub board: Color,
+    pub placeholder: Color,
+    /// Tile colours for 2, 4, 8, ...; bigger tiles reuse the last one.
+    pub tiles: Vec<Color>,
+    pub text: Color,
+    /// Font asset path, or the built-in font when unset.
+    #[serde(default)]
+    pub font: Option<String>,
+    /// Corner radius as a fraction of the tile size, from 0.0 to 0.5.
+    #[serde(default)]
+    pub corner_radius: f32,
+    /// Space between tiles as a fraction of the tile size.
+    pub spacing: f32,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            name: "built-in".to_string(),
+            background: Color::hex("#1f2638").unwrap(),
+            board: colors::BOARD,
+            placeholder: colors::TILE_PLACEHOLDER,
+            tiles: vec![colors::TILE],
+            text: Color::BLACK,
+            font: None,
+            corner_radius: 0.0,
+            spacing: 0.25,
+        }
+    }
+}
+
+impl Theme {
+    pub fn tile_color(&self, value: u32) -> Color {
+        let index = value.max(2).ilog2() as usize - 1;
+        self.tiles
+            .get(index)
+            .or(self.tiles.last())
+            .copied()
+            .unwrap_or(colors::TILE)
+    }
+}
+
+#[derive(Default)]
+pub struct ThemeLoader;
+
+impl AssetLoader for ThemeLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let theme: Theme = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(theme));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["theme.ron"]
+    }
+}
+
+/// The theme currently on screen, along with the assets derived from it.
+#[derive(Resource)]
+pub struct ActiveTheme {
+    pub theme: Theme,
+    pub font: Handle<Font>,
+    /// A white rounded square to tint for the board and its tiles.
+    pub corners: Handle<Image>,
+}
+
+impl Default for ActiveTheme {
+    fn default() -> Self {
+        ActiveTheme {
+            theme: Theme::default(),
+            font: Handle::default(),
+            corners: DEFAULT_IMAGE_HANDLE.typed(),
+        }
+    }
+}
+
+#[derive(Resource)]
+struct Themes {
+    handles: Vec<Handle<Theme>>,
+    current: usize,
+}
+
+/// Loads the built-in themes, starting with the configured one. A theme that
+/// isn't built in joins the end of the cycle.
+fn load_themes(mut commands: Commands, asset_server: Res<AssetServer>, config: Res<BoxesConfig>) {
+    let mut paths: Vec<&str> = THEME_FILES.to_vec();
+    let current = match config.theme.as_deref() {
+        Some(theme) => paths
+            .iter()
+            .position(|path| *path == theme)
+            .unwrap_or_else(|| {
+                paths.push(theme);
+                paths.len() - 1
+            }),
+        None => 0,
+    };
+
+    commands.insert_resource(Themes {
+        handles: paths.iter().map(|path| asset_server.load(*path)).collect(),
+        current,
+    });
+}
+
+fn cycle_themes(keys: Res<Input<KeyCode>>, mut themes: ResMut<Themes>) {
+    if keys.just_pressed(KeyCode::T) {
+        themes.current = (themes.current + 1) % themes.handles.len();
+    }
+}
+
+/// Everything `apply_theme` writes the theme into.
+#[derive(SystemParam)]
+struct ThemeTargets<'w> {
+    images: ResMut<'w, Assets<Image>>,
+    active: ResMut<'w, ActiveTheme>,
+    clear_color: ResMut<'w, ClearColor>,
+}
+
+/// Copies the selected theme into `ActiveTheme` whenever the selection
+/// changes or its file is (re)loaded, which is what makes hot-reloading work.
+/// The accessibility palette, if any, is applied on top.
+fn apply_theme(
+    themes: Res<Themes>,
+    theme_assets: Res<Assets<Theme>>,
+    mut theme_events: EventReader<AssetEvent<Theme>>,
+    asset_server: Res<AssetServer>,
+    accessibility: Res<AccessibilitySettings>,
+    mut targets: ThemeTargets,
+) {
+    let handle = &themes.handles[themes.current];
+    let reloaded = theme_events.iter().any(|event| match event {
+        AssetEvent::Created { handle: changed } | AssetEvent::Modified { handle: changed } => {
+            changed == handle
+        }
+        AssetEvent::Removed { .. } => false,
+    });
+    if !reloaded && !themes.is_changed() && !accessibility.is_changed() {
+        return;
+    }
+    let Some(theme) = theme_assets.get(handle) else {
+        return;
+    };
+
+    let mut theme = theme.clone();
+    accessibility.palette.apply(&mut theme);
+
+    targets.clear_color.0 = theme.background;
+    targets.active.font = match &theme.font {
+        Some(path) => asset_server.load(path.as_str()),
+        None => Handle::default(),
+    };
+    targets.active.corners = targets.images.add(rounded_square(theme.corner_radius));
+    targets.active.theme = theme;
+}
+
+fn rounded_square(corner_radius: f32) -> Image {
+    let size = CORNER_TEXTURE_SIZE as f32;
+    let radius = corner_radius.clamp(0.0, 0.5) * size;
+    let mut data = Vec::with_capacity((CORNER_TEXTURE_SIZE * CORNER_TEXTURE_SIZE * 4) as usize);
+
+    for y in 0..CORNER_TEXTURE_SIZE {
+        for x in 0..CORNER_TEXTURE_SIZE {
+            // Distance outside the rounded corner, anti-aliased over a pixel.
+            let px = (x as f32 + 0.5).min(size - x as f32 - 0.5);
+            let py = (y as f32 + 0.5).min(size - y as f32 - 0.5);
+            let alpha = if px < radius && py < radius {
+                let distance = Vec2::new(radius - px, radius - py).length();
+                (radius - distance + 0.5).clamp(0.0, 1.0)
+            } else {
+                1.0
+            };
+            data.extend_from_slice(&[255, 255, 255, (alpha * 255.0) as u8]);
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: CORNER_TEXTURE_SIZE,
+            height: CORNER_TEXTURE_SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
@@ -0,0 +1,173 @@
+//! A headless harness for driving `BoxesPlugin` from integration tests.
+//!
+//! Boards are written as ASCII art, top row first, with `.` for empty cells:
+//!
+//! ```text
+//! 2 . . .
+//! . . 4 .
+//! . . . .
+//! . . . 2
+//! ```
+
+use bevy::{
+    ecs::event::ManualEventReader,
+    input::{keyboard::KeyboardInput, ButtonState, InputPlugin},
+    prelude::*,
+};
+use boxes::board::Position;
+use boxes::events::TileSpawned;
+use boxes::game::{Game, Grid, Rules};
+use boxes::{BoxesConfig, BoxesPlugin, CurrentGame, InputMode, Points};
+
+/// Any seed will do, as long as runs are repeatable.
+const SEED: u64 = 2048;
+
+pub struct Harness {
+    pub app: App,
+    spawned_reader: ManualEventReader<TileSpawned>,
+    spawned: Vec<((u8, u8), u32)>,
+}
+
+impl Harness {
+    /// Starts a game from `board` under the default rules.
+    pub fn new(board: &str) -> Self {
+        Harness::with_rules(board, Rules::default())
+    }
+
+    pub fn with_rules(board: &str, rules: Rules) -> Self {
+        let grid = parse_grid(board);
+        let game = Game::resume(grid, 0, SEED, 0, rules);
+
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, InputPlugin))
+            .add_plugins(BoxesPlugin {
+                config: BoxesConfig {
+                    size: game.grid.size(),
+                    seed: SEED,
+                    rules,
+                    start: Some(game),
+                    theme: None,
+                    input: InputMode::Keyboard,
+                },
+            });
+
+        let mut harness = Harness {
+            app,
+            spawned_reader: ManualEventReader::default(),
+            spawned: Vec::new(),
+        };
+        harness.step(1);
+        harness.spawned.clear();
+        harness
+    }
+
+    /// Runs `updates` frames of the app.
+    pub fn step(&mut self, updates: usize) -> &mut Self {
+        for _ in 0..updates {
+            self.app.update();
+
+            let events = self.app.world.resource::<Events<TileSpawned>>();
+            self.spawned.extend(
+                self.spawned_reader
+                    .iter(events)
+                    .map(|spawned| (spawned.cell, spawned.value)),
+            );
+        }
+        self
+    }
+
+    /// Taps `key`, as a keyboard would, then runs one frame.
+    pub fn press(&mut self, key: KeyCode) -> &mut Self {
+        let window = Entity::PLACEHOLDER;
+        for state in [ButtonState::Pressed, ButtonState::Released] {
+            self.app.world.send_event(KeyboardInput {
+                scan_code: 0,
+                key_code: Some(key),
+                state,
+                window,
+            });
+        }
+        self.step(1)
+    }
+
+    pub fn game(&self) -> &Game {
+        self.app.world.resource::<CurrentGame>()
+    }
+
+    /// Tiles spawned since the last call, as `(cell, value)`.
+    pub fn take_spawned(&mut self) -> Vec<((u8, u8), u32)> {
+        std::mem::take(&mut self.spawned)
+    }
+
+    /// The board as the tile entities show it.
+    pub fn board(&mut self) -> Grid {
+        let size = self.game().grid.size();
+        let mut grid = Grid::new(size);
+        let mut tiles = self.app.world.query::<(&Position, &Points)>();
+
+        for (pos, points) in tiles.iter(&self.app.world) {
+            let cell = (pos.x, pos.y);
+            assert_eq!(
+                grid.get(cell),
+                None,
+                "two tiles at {cell:?} in\n{}",
+                format_grid(&grid)
+            );
+            grid.set(cell, Some(points.value));
+        }
+        grid
+    }
+
+    /// Checks the tile entities against `expected`, ignoring the tiles spawned
+    /// since the last check, and that they agree with the game core.
+    pub fn assert_board(&mut self, expected: &str) -> &mut Self {
+        let mut board = self.board();
+        assert_eq!(
+            format_grid(&board),
+            format_grid(&self.game().grid),
+            "tile entities out of sync with the game"
+        );
+
+        for (cell, _) in self.take_spawned() {
+            board.set(cell, None);
+        }
+        assert_eq!(format_grid(&board), format_grid(&parse_grid(expected)));
+        self
+    }
+}
+
+pub fn parse_grid(board: &str) -> Grid {
+    let rows: Vec<Vec<&str>> = board
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .filter(|row| !row.is_empty())
+        .collect();
+    let size = rows.len() as u8;
+    let mut grid = Grid::new(size);
+
+    for (row, values) in rows.iter().enumerate() {
+        assert_eq!(values.len(), rows.len(), "board isn't square: {board}");
+        let y = size - 1 - row as u8;
+        for (x, value) in values.iter().enumerate() {
+            let value = match *value {
+                "." => None,
+                value => Some(value.parse().expect("tile values are numbers or '.'")),
+            };
+            grid.set((x as u8, y), value);
+        }
+    }
+    grid
+}
+
+pub fn format_grid(grid: &Grid) -> String {
+    (0..grid.size())
+        .rev()
+        .map(|y| {
+            (0..grid.size())
+                .map(|x| grid.get((x, y)).map_or(".".to_string(), |v| v.to_string()))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,341 @@
+mod common;
+
+use bevy::prelude::{Events, KeyCode};
+use boxes::events::{BoardEdited, GameEnded};
+use boxes::game::{Game, Mode, Rules};
+use boxes::CurrentGame;
+use common::{format_grid, parse_grid, Harness};
+
+#[test]
+fn starting_tiles_are_spawned() {
+    let board = "
+        . . . .
+        . 2 . .
+        . . . .
+        . . 4 .
+    ";
+    let mut harness = Harness::new(board);
+
+    harness.assert_board(board);
+    assert_eq!(harness.game().moves, 0);
+}
+
+#[test]
+fn moves_spawn_one_tile_in_an_empty_cell() {
+    let mut harness = Harness::new(
+        "
+        . . . .
+        . 2 . .
+        . . . .
+        . . 4 .
+    ",
+    );
+    let slid = parse_grid(
+        "
+        . . . .
+        2 . . .
+        . . . .
+        4 . . .
+    ",
+    );
+
+    let spawned = harness.press(KeyCode::Left).take_spawned();
+
+    assert_eq!(spawned.len(), 1);
+    let (cell, value) = spawned[0];
+    assert_eq!(slid.get(cell), None, "spawned on top of a tile at {cell:?}");
+    assert!(value == 2 || value == 4);
+
+    let mut expected = slid;
+    expected.set(cell, Some(value));
+    assert_eq!(format_grid(&harness.board()), format_grid(&expected));
+}
+
+#[test]
+fn tiles_slide_to_the_edge() {
+    let mut harness = Harness::new(
+        "
+        2 . . .
+        . . 4 .
+        . . . .
+        . 8 . 2
+    ",
+    );
+
+    harness.press(KeyCode::Right).assert_board(
+        "
+        . . . 2
+        . . . 4
+        . . . .
+        . . 8 2
+    ",
+    );
+    harness.step(3).assert_board(
+        "
+        . . . 2
+        . . . 4
+        . . . .
+        . . 8 2
+    ",
+    );
+    assert_eq!(harness.game().moves, 1);
+}
+
+#[test]
+fn equal_tiles_merge_once_per_move() {
+    let mut harness = Harness::new(
+        "
+        2 2 4 4
+        . . . .
+        2 . 2 2
+        8 8 8 8
+    ",
+    );
+
+    harness.press(KeyCode::Left).assert_board(
+        "
+        4 8 . .
+        . . . .
+        4 2 . .
+        16 16 . .
+    ",
+    );
+    assert_eq!(harness.game().score, 48);
+}
+
+#[test]
+fn blocked_moves_change_nothing() {
+    let board = "
+        2 . . .
+        4 . . .
+        . . . .
+        . . . .
+    ";
+    let mut harness = Harness::new(board);
+
+    harness.press(KeyCode::Left).assert_board(board);
+    assert!(harness.take_spawned().is_empty());
+    assert_eq!(harness.game().moves, 0);
+}
+
+#[test]
+fn undo_restores_the_previous_board() {
+    let board = "
+        . . . .
+        . 2 . .
+        . . . .
+        2 . . .
+    ";
+    let mut harness = Harness::new(board);
+
+    harness.press(KeyCode::Down).press(KeyCode::Back);
+    // Undoing respawns every tile, none of which are new.
+    harness.take_spawned();
+
+    harness.assert_board(board);
+    assert_eq!(harness.game().score, 0);
+}
+
+#[test]
+fn filling_the_board_ends_the_game() {
+    // Only 2s spawn, so the last free cell can't offer a merge.
+    let rules = Rules {
+        four_chance: 0.0,
+        ..Rules::default()
+    };
+    let mut harness = Harness::with_rules(
+        "
+        2 4 2
+        4 2 4
+        8 16 .
+    ",
+        rules,
+    );
+
+    harness.press(KeyCode::Right);
+    assert_eq!(harness.take_spawned(), vec![((0, 0), 2)]);
+    assert!(harness.game().over);
+    assert!(!harness.game().won);
+
+    harness.press(KeyCode::Left).assert_board(
+        "
+        2 4 2
+        4 2 4
+        2 8 16
+    ",
+    );
+    assert_eq!(harness.game().moves, 1);
+
+    // The loss has been counted, so it can't be taken back.
+    harness.press(KeyCode::Back).assert_board(
+        "
+        2 4 2
+        4 2 4
+        2 8 16
+    ",
+    );
+    assert!(harness.game().over);
+}
+
+#[test]
+fn restarting_a_played_game_abandons_it() {
+    let mut harness = Harness::new(
+        "
+        . . . .
+        . 2 . .
+        . . . .
+        2 . . .
+    ",
+    );
+    harness.press(KeyCode::Down).press(KeyCode::R);
+
+    let events = harness.app.world.resource::<Events<GameEnded>>();
+    let ended: Vec<&GameEnded> = events.get_reader().iter(events).collect();
+    assert_eq!(ended.len(), 1);
+    assert!(ended[0].abandoned);
+    assert!(!ended[0].won);
+}
+
+#[test]
+fn reaching_the_winning_tile_wins() {
+    let rules = Rules {
+        winning_tile: 8,
+        ..Rules::default()
+    };
+    let mut harness = Harness::with_rules(
+        "
+        . . .
+        . . .
+        4 4 .
+    ",
+        rules,
+    );
+
+    harness.press(KeyCode::Left).assert_board(
+        "
+        . . .
+        . . .
+        8 . .
+    ",
+    );
+    assert!(harness.game().won);
+    assert!(harness.game().over);
+}
+
+#[test]
+fn edited_boards_are_rebuilt() {
+    let mut harness = Harness::new(
+        "
+        . . . .
+        . . . .
+        . . . .
+        2 . . .
+    ",
+    );
+
+    {
+        let mut game = harness.app.world.resource_mut::<CurrentGame>();
+        game.set_tile((0, 0), None);
+        game.set_tile((2, 1), Some(64));
+        game.set_tile((3, 3), Some(8));
+    }
+    harness.app.world.send_event(BoardEdited);
+    harness.step(1).take_spawned();
+
+    harness.assert_board(
+        "
+        . . . 8
+        . . . .
+        . . 64 .
+        . . . .
+    ",
+    );
+}
+
+#[test]
+fn editing_into_a_dead_board_ends_the_game() {
+    let mut harness = Harness::new(
+        "
+        2 4 2
+        4 2 4
+        2 8 .
+    ",
+    );
+
+    assert!(harness
+        .app
+        .world
+        .resource_mut::<CurrentGame>()
+        .set_tile((2, 0), Some(16)));
+    harness.app.world.send_event(BoardEdited);
+    harness.step(1);
+
+    assert!(harness.game().over);
+    let events = harness.app.world.resource::<Events<GameEnded>>();
+    let ended: Vec<&GameEnded> = events.get_reader().iter(events).collect();
+    assert_eq!(ended.len(), 1);
+    assert!(!ended[0].won);
+}
+
+#[test]
+fn finished_games_cant_be_edited() {
+    let rules = Rules {
+        winning_tile: 8,
+        ..Rules::default()
+    };
+    let mut harness = Harness::with_rules(
+        "
+        . . .
+        . . .
+        4 4 .
+    ",
+        rules,
+    );
+    harness.press(KeyCode::Left);
+    assert!(harness.game().over);
+
+    let mut game = harness.app.world.resource_mut::<CurrentGame>();
+    let before = format_grid(&game.grid);
+    assert!(!game.set_tile((0, 0), Some(2)));
+    assert!(game.over);
+    assert_eq!(format_grid(&game.grid), before);
+}
+
+#[test]
+fn hand_edits_make_the_game_custom() {
+    let edits: [fn(&mut Game); 3] = [
+        |game| {
+            game.set_tile((0, 0), Some(2));
+        },
+        |game| game.force_next_spawn(Some(((0, 0), 4))),
+        |game| game.step_rng(),
+    ];
+    for edit in edits {
+        let mut game = Game::new(4, 7);
+        assert_eq!(game.mode(), Mode::Classic);
+        edit(&mut game);
+        assert_eq!(game.mode(), Mode::Custom);
+    }
+}
+
+#[test]
+fn forced_spawns_land_where_asked() {
+    let mut harness = Harness::new(
+        "
+        . . . .
+        . . . .
+        . . . .
+        2 . . .
+    ",
+    );
+    harness
+        .app
+        .world
+        .resource_mut::<CurrentGame>()
+        .force_next_spawn(Some(((3, 3), 4)));
+
+    harness.press(KeyCode::Right);
+
+    assert_eq!(harness.take_spawned(), vec![((3, 3), 4)]);
+    assert_eq!(harness.game().forced_spawn(), None);
+}